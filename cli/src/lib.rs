@@ -1,22 +1,56 @@
+mod opts;
+
+pub use opts::Opts;
+
 use {
     async_std::task::block_on,
     indicatif::{MultiProgress, ProgressBar, ProgressStyle},
-    std::{
-        ffi::OsStr,
-        fs::{create_dir, read_dir},
-        path::{Path, PathBuf},
+    std::{ffi::OsStr, fs::create_dir, mem::take, path::PathBuf, sync::Arc},
+    video_mosaic_crawler::{
+        download_urls, gen_client, get_urls, Download, DownloadConfig, ImageSource, Limiter,
+        Result, Source, SourceConfig,
+    },
+    video_mosaic_diff::{
+        sample_frames, scan_library, LibItem, LibraryConfig, ProcessConfig, ProcessWrapper,
+        IMAGE_FILTER, VIDEO_FILTER,
     },
-    video_mosaic_crawler::{download_urls, gen_client, get_urls},
-    video_mosaic_diff::{ProcessConfig, ProcessWrapper, IMAGE_FILTER, VIDEO_FILTER},
 };
 
+/// What to crawl into the library
+#[derive(Debug, Clone)]
+pub struct CrawlConfig {
+    pub keyword: Vec<String>,
+    /// The number of images of each keyword
+    pub num: usize,
+    pub source: Source,
+    pub source_config: SourceConfig,
+    /// Crawls the images of each keyword for the given number of colors of the target that
+    /// the library covers worst, shared out by how poorly they are covered
+    pub fill_gaps: Option<usize>,
+    /// The number of frames of a video analysed for the gaps
+    pub gap_frames: usize,
+    pub download: DownloadConfig,
+    /// Continues the downloads into the last folder of each keyword
+    pub resume: bool,
+}
+
 pub fn run(
     target: PathBuf,
-    keyword: Vec<String>,
-    num: usize,
     library: Vec<PathBuf>,
+    scan: LibraryConfig,
+    crawl: CrawlConfig,
     config: ProcessConfig,
-) {
+) -> Result<()> {
+    let CrawlConfig {
+        keyword,
+        num,
+        source,
+        source_config,
+        fill_gaps,
+        gap_frames,
+        download,
+        resume,
+    } = crawl;
     if library.is_empty() && keyword.is_empty() {
         panic!(
             r#"The following required arguments were not provided:
@@ -37,29 +71,91 @@ pub fn run(
         }
     };
 
-    let mut libraries = Vec::with_capacity(keyword.len() * num);
-    for lib in library {
-        push_file_to_lib(&mut libraries, &lib);
+    let mut libraries = scan_library(&library, &scan)?;
+
+    let ext = OsStr::new(if video { "mp4" } else { "png" });
+    let mut path = target.clone();
+    let mut base = target.file_stem().unwrap().to_os_string();
+    base.push("-mosaic");
+    path.set_file_name(&base);
+    path.set_extension(ext);
+    while path.exists() {
+        base.push("_");
+        path.set_file_name(&base);
+        path.set_extension(ext);
+    }
+
+    let size = config.size as u64;
+    let mut proc = ProcessWrapper::new(
+        config,
+        target.to_string_lossy().to_string(),
+        path.to_string_lossy().to_string(),
+        video,
+    )?;
+    for warning in proc.warnings() {
+        eprintln!("Warning: {}", warning);
     }
 
+    // the gaps are those of the local library
+    let (mut lib_color, mut lib_image): (Vec<_>, Vec<_>) =
+        match fill_gaps.is_some() && !keyword.is_empty() {
+            true => block_on(index(&proc, take(&mut libraries)))?,
+            false => Vec::new(),
+        }
+        .into_iter()
+        .unzip();
+
     if !keyword.is_empty() {
         println!("Crawling images:");
-        let client = gen_client();
+        let client = gen_client()?;
+        let limiter = Limiter::new(download);
 
-        for keyword in keyword {
+        let searches: Vec<(Arc<dyn ImageSource>, String, usize)> = match fill_gaps {
+            Some(colors) => {
+                let frames = sample_frames(&target, video, gap_frames)?;
+                let mut gaps = proc.color_gaps(&frames, &lib_color);
+                gaps.truncate(colors);
+                if gaps.is_empty() {
+                    println!("The library covers the colors of the target");
+                }
+                let sum = gaps.iter().map(|(_, score)| score).sum::<f32>();
+                let mut searches = Vec::with_capacity(keyword.len() * gaps.len());
+                for keyword in &keyword {
+                    for (color, score) in &gaps {
+                        let (source, keyword) = source.colored(&source_config, keyword, color)?;
+                        let num = ((num as f32 * score / sum).round() as usize).max(1);
+                        searches.push((source, keyword, num));
+                    }
+                }
+                searches
+            }
+            None => {
+                let source = source.build(&source_config)?;
+                keyword
+                    .into_iter()
+                    .map(|keyword| (source.clone(), keyword, num))
+                    .collect()
+            }
+        };
+
+        for (source, keyword, num) in searches {
             let mut folder = PathBuf::new();
+            let mut last = None;
             for i in 0.. {
                 folder = PathBuf::from(format!("{}-{}", keyword, i));
                 if !folder.exists() {
                     break;
                 }
+                last = Some(folder.clone());
+            }
+            match last {
+                Some(last) if resume => folder = last,
+                _ => create_dir(&folder)?,
             }
-            create_dir(&folder).unwrap();
 
             block_on(async {
-                let (num, tasks) = get_urls(client.clone(), keyword.clone(), num)
-                    .await
-                    .unwrap();
+                let (num, tasks) =
+                    get_urls(client.clone(), source.clone(), keyword.clone(), num).await?;
                 let mut urls = Vec::with_capacity(num);
                 for task in tasks {
                     if let Ok(ret) = task.await {
@@ -69,93 +165,88 @@ pub fn run(
 
                 let pb = gen_progress_bar(keyword.as_str(), num as u64);
 
-                let tasks = download_urls(client.clone(), urls, folder.clone());
+                let tasks = download_urls(
+                    client.clone(),
+                    source.clone(),
+                    limiter.clone(),
+                    &keyword,
+                    urls,
+                    folder.clone(),
+                )?;
+                let mut failed = 0;
                 for task in tasks {
-                    let _ = task.await;
+                    match task.await {
+                        Ok(Download::Saved) => (),
+                        Ok(Download::Rejected(rejection)) => {
+                            pb.println(format!("{}: rejected, {}", keyword, rejection))
+                        }
+                        Err(_) => failed += 1,
+                    }
                     pb.inc(1);
                 }
                 pb.finish();
-            });
+                if failed > 0 {
+                    println!("{} of {} images of {} failed", failed, num, keyword);
+                }
+                Result::<()>::Ok(())
+            })?;
 
-            push_file_to_lib(&mut libraries, &folder);
+            libraries.extend(scan_library(&[folder], &LibraryConfig::default())?);
         }
     }
 
     println!("Processing image:");
 
-    let ext = OsStr::new(if video { "mp4" } else { "png" });
-    let mut path = target.clone();
-    let mut base = target.file_stem().unwrap().to_os_string();
-    base.push("-mosaic");
-    path.set_file_name(&base);
-    path.set_extension(ext);
-    while path.exists() {
-        base.push("_");
-        path.set_file_name(&base);
-        path.set_extension(ext);
-    }
-
-    let mut proc = ProcessWrapper::new(
-        config,
-        target.to_string_lossy().to_string(),
-        path.to_string_lossy().to_string(),
-        video,
-    );
-
-    let index = gen_progress_bar("Index", libraries.len() as u64);
     let m = MultiProgress::new();
     let fill = m.add(gen_progress_bar(
         "Fill",
-        (proc.width() as u64 / config.size as u64 + 1)
-            * (proc.height() as u64 / config.size as u64 + 1),
+        (proc.width() as u64 / size + 1) * (proc.height() as u64 / size + 1),
     ));
     let total = m.add(gen_progress_bar("Total", proc.frames() as u64));
 
     block_on(async move {
-        let mut lib_color = Vec::with_capacity(libraries.len());
-        let mut lib_image = Vec::with_capacity(libraries.len());
-        let tasks = proc.index(libraries);
-        for task in tasks {
-            if let Some((color, image)) = task.await {
-                lib_color.push(color);
-                lib_image.push(image);
-            }
-            index.inc(1);
+        for (color, image) in index(&proc, libraries).await? {
+            lib_color.push(color);
+            lib_image.push(image);
+        }
+        let removed = proc.post_index(lib_color, lib_image)?;
+        if removed > 0 {
+            println!("Removed {} near duplicates from the library", removed);
         }
-        proc.post_index(lib_color, lib_image);
-        index.finish();
 
-        while proc.pre_fill() {
+        while proc.pre_fill()? {
             fill.reset();
             let tasks = proc.fill();
             for task in tasks {
-                let (mask, replace_idx) = task.await;
-                proc.post_fill_step(mask, replace_idx);
-                fill.inc(1);
+                for (mask, replace_idx) in task.await? {
+                    proc.post_fill_step(mask, replace_idx);
+                    fill.inc(1);
+                }
             }
-            proc.post_fill();
+            proc.post_fill()?;
             fill.finish();
             total.inc(1);
         }
         total.finish();
-    });
+        Result::<()>::Ok(())
+    })
 }
 
-fn push_file_to_lib(library: &mut Vec<PathBuf>, folder: &Path) {
-    for entry in read_dir(folder).unwrap().flatten() {
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
-        }
-        let ext = path
-            .extension()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default();
-        if IMAGE_FILTER.contains(&ext) {
-            library.push(path)
-        }
+async fn index(proc: &ProcessWrapper, libraries: Vec<PathBuf>) -> Result<Vec<LibItem>> {
+    let pb = gen_progress_bar("Index", libraries.len() as u64);
+    let mut chunks = Vec::new();
+    for task in proc.index(libraries) {
+        let (idx, ret) = task.await?;
+        pb.inc(ret.len() as u64);
+        chunks.push((idx, ret));
     }
+    pb.finish();
+    // the same library in the same order with the same seed
+    chunks.sort_unstable_by_key(|(idx, _)| *idx);
+    Ok(chunks
+        .into_iter()
+        .flat_map(|(_, ret)| ret.into_iter().flatten())
+        .collect())
 }
 
 fn gen_progress_bar(title: &str, total: u64) -> ProgressBar {
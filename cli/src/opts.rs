@@ -0,0 +1,370 @@
+use {
+    crate::{run, CrawlConfig},
+    argh::FromArgs,
+    std::{path::PathBuf, time::Duration},
+    video_mosaic_crawler::{
+        str2backoff, str2color, str2image_type, str2resolution, str2source, DownloadConfig,
+        ImageType, Resolution, Result, Source, SourceConfig,
+    },
+    video_mosaic_diff::{
+        str2audio, str2cmc, str2cs, str2cu, str2da, str2filter, str2scaling, str2time, str2weights,
+        Audio, CalculationUnit, ColorSpace, DistanceAlgorithm, Filter, LibraryConfig,
+        ProcessConfig, QualityFilter, Scaling, Symlinks,
+    },
+};
+
+/// Video Mosaic CLI.
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "cli")]
+pub struct Opts {
+    /// the path of the target file
+    #[argh(positional)]
+    target: PathBuf,
+    /// keywords to crawl the images
+    #[argh(option, short = 'k')]
+    keyword: Vec<String>,
+    /// the number of images that need to be crawled
+    #[argh(option, short = 'n', default = "100")]
+    num: usize,
+    /// the provider of the crawled images (baidu, pixabay, json, manifest)
+    #[argh(option, default = "Source::default()", from_str_fn(str2source))]
+    source: Source,
+    /// the base url of the provider, the url template of the json endpoint or the path of
+    /// the manifest
+    #[argh(option)]
+    source_url: Option<String>,
+    /// the API key of the provider (PIXABAY_API_KEY by default)
+    #[argh(option)]
+    api_key: Option<String>,
+    /// only crawl images of the color, can be repeated (pixabay)
+    #[argh(option, from_str_fn(str2color))]
+    color: Vec<String>,
+    /// the type of the crawled images (all, photo, illustration, vector) (pixabay)
+    #[argh(option, default = "ImageType::default()", from_str_fn(str2image_type))]
+    image_type: ImageType,
+    /// the number of images requested at once (pixabay)
+    #[argh(option, default = "40")]
+    per_page: usize,
+    /// the size of the crawled images (preview, small, medium, large) (pixabay)
+    #[argh(option, default = "Resolution::default()", from_str_fn(str2resolution))]
+    resolution: Resolution,
+    /// the JSONPath to the image urls of the json endpoint (e.g. $.hits[*].url)
+    #[argh(option)]
+    json_path: Option<String>,
+    /// the JSONPath to the number of results of the json endpoint
+    #[argh(option)]
+    total_path: Option<String>,
+    /// crawl each keyword for the given number of colors of the target that the library
+    /// covers worst
+    #[argh(option)]
+    fill_gaps: Option<usize>,
+    /// the number of frames of the video analysed for the gaps
+    #[argh(option, default = "8")]
+    gap_frames: usize,
+    /// the number of retries of a failed download
+    #[argh(option, default = "3")]
+    retries: u32,
+    /// the delay in seconds before the first retry, doubled by each one
+    #[argh(option, default = "Duration::from_secs(1)", from_str_fn(str2backoff))]
+    backoff: Duration,
+    /// the maximum number of requests per second to the same host
+    #[argh(option)]
+    rate: Option<f32>,
+    /// continue the downloads into the last folder of each keyword
+    #[argh(switch)]
+    resume: bool,
+    /// the minimum file size in bytes of the crawled images
+    #[argh(option, default = "0")]
+    min_bytes: usize,
+    /// the maximum file size in bytes of the crawled images
+    #[argh(option, default = "20 << 20")]
+    max_bytes: usize,
+    /// the minimum length in pixels of the shortest side of the crawled images
+    #[argh(option, default = "16")]
+    min_side: u32,
+    /// the maximum length in pixels of the longest side of the crawled images
+    #[argh(option, default = "8192")]
+    max_side: u32,
+    /// remove the library images whose perceptual hashes differ from an earlier one in at
+    /// most the given number of bits (e.g. 4)
+    #[argh(option)]
+    dedup: Option<u32>,
+    /// drop the library images whose Laplacian variance is below the given value, i.e. the
+    /// blurry ones (e.g. 100)
+    #[argh(option)]
+    min_sharpness: Option<f32>,
+    /// drop the library images whose luma standard deviation is below the given value, i.e.
+    /// the nearly uniform ones (e.g. 10)
+    #[argh(option)]
+    min_contrast: Option<f32>,
+    /// drop the library images with more than the given share of pixels in their four most
+    /// frequent colors, i.e. text and screenshots (e.g. 0.6)
+    #[argh(option)]
+    max_flat_share: Option<f32>,
+    /// drop the library images whose longer side exceeds the shorter one by more than the
+    /// given ratio (e.g. 3)
+    #[argh(option)]
+    max_aspect_ratio: Option<f32>,
+    /// the paths of the libraries, folders scanned recursively or images
+    #[argh(option, short = 'l')]
+    library: Vec<PathBuf>,
+    /// the depth of the library subfolders scanned, 0 for the library folders only
+    #[argh(option)]
+    max_depth: Option<usize>,
+    /// follow the symbolic links in the libraries
+    #[argh(switch)]
+    follow_symlinks: bool,
+    /// glob patterns of the library images used, of the path relative to the library or of
+    /// the file name if there is no '/' (e.g. "**/2021/*")
+    #[argh(option)]
+    include: Vec<String>,
+    /// glob patterns of the library images and folders skipped, as --include (e.g. thumbs)
+    #[argh(option)]
+    exclude: Vec<String>,
+    /// sample the given number of library images at random with the seed if there are more
+    #[argh(option)]
+    max_files: Option<usize>,
+    /// the size of the block
+    #[argh(option, short = 's', default = "50")]
+    size: u16,
+    /// k-means (k)
+    #[argh(option, default = "1")]
+    k: u8,
+    /// use Hamerly’s K-Means Clustering Algorithm
+    #[argh(switch, short = 'h')]
+    hamerly: bool,
+    /// k-means seed
+    #[argh(option, default = "0")]
+    seed: u64,
+    /// the number of k-means runs with successive seeds
+    #[argh(option, default = "3")]
+    runs: u8,
+    /// k-means convergence factor
+    #[argh(option)]
+    converge: Option<f32>,
+    /// k-means max iterations
+    #[argh(option)]
+    max_iter: Option<usize>,
+    /// calculation unit (average, pixel, k_means)
+    #[argh(option, default = "CalculationUnit::default()", from_str_fn(str2cu))]
+    calc_unit: CalculationUnit,
+    /// color space (rgb, hsv, cielab, cielch, oklab, ycbcr, xyz)
+    #[argh(option, default = "ColorSpace::default()", from_str_fn(str2cs))]
+    color_space: ColorSpace,
+    /// distance algorithm (euclidean, weighted_euclidean, cie76, cie94, cie94_textiles, cmc,
+    /// ciede2000)
+    #[argh(option, default = "DistanceAlgorithm::default()", from_str_fn(str2da))]
+    dist_algo: DistanceAlgorithm,
+    /// channel weights of the weighted euclidean distance (e.g. 2,1,1)
+    #[argh(option, default = "[1.; 3]", from_str_fn(str2weights))]
+    weights: [f32; 3],
+    /// lightness and chroma factors of CMC l:c (e.g. 2:1)
+    #[argh(option, default = "(2., 1.)", from_str_fn(str2cmc))]
+    cmc: (f32, f32),
+    /// filter (nearest, triangle, catmullRom, gaussian, lanczos3)
+    #[argh(option, default = "Filter::default()", from_str_fn(str2filter))]
+    filter: Filter,
+    /// the number of iterations of the quadrant
+    #[argh(option)]
+    quad_iter: Option<usize>,
+    /// overlay image and set the bottom image's alpha channel
+    #[argh(option)]
+    overlay: Option<u8>,
+    /// the number of frames decoded and matched ahead
+    #[argh(option, default = "4")]
+    lookahead: u8,
+    /// the number of threads matching the masks (all cores by default)
+    #[argh(option)]
+    threads: Option<usize>,
+    /// scaling algorithm of the video frames (fast_bilinear, bilinear, bicubic, lanczos,
+    /// spline)
+    #[argh(option, default = "Scaling::default()", from_str_fn(str2scaling))]
+    scaling: Scaling,
+    /// start of the rendered section of the video (e.g. 1:30 or 90.5)
+    #[argh(option, from_str_fn(str2time))]
+    start: Option<f64>,
+    /// end of the rendered section of the video (e.g. 1:40 or 100)
+    #[argh(option, from_str_fn(str2time))]
+    end: Option<f64>,
+    /// the histogram difference (0 to 1) of two frames from which on the whole frame is
+    /// filled again
+    #[argh(option, default = "0.4")]
+    scene_cut: f32,
+    /// the color difference of a tile, relative to the one of black and white, from which on
+    /// it is filled again
+    #[argh(option, default = "0.02")]
+    change_threshold: f32,
+    /// render every n-th frame of the video
+    #[argh(option, default = "1")]
+    frame_step: usize,
+    /// the maximum number of rendered frames of the video
+    #[argh(option)]
+    max_frames: Option<usize>,
+    /// what happens to the audio of the video (keep, drop, aac, opus)
+    #[argh(option, default = "Audio::default()", from_str_fn(str2audio))]
+    audio: Audio,
+    /// replaces the audio of the video with the one of this file
+    #[argh(option)]
+    audio_file: Option<PathBuf>,
+    /// leave out the subtitles of the video
+    #[argh(switch)]
+    no_subtitles: bool,
+    /// fail on the videos that tell no frame rate instead of assuming 25 fps
+    #[argh(switch)]
+    strict_frame_rate: bool,
+}
+
+// also the top-level command of the cli binary
+impl argh::TopLevelCommand for Opts {}
+
+impl Opts {
+    pub fn run(self) -> Result<()> {
+        let Opts {
+            target,
+            keyword,
+            num,
+            source,
+            source_url,
+            api_key,
+            color,
+            image_type,
+            per_page,
+            resolution,
+            json_path,
+            total_path,
+            fill_gaps,
+            gap_frames,
+            retries,
+            backoff,
+            rate,
+            resume,
+            min_bytes,
+            max_bytes,
+            min_side,
+            max_side,
+            dedup,
+            min_sharpness,
+            min_contrast,
+            max_flat_share,
+            max_aspect_ratio,
+            library,
+            max_depth,
+            follow_symlinks,
+            include,
+            exclude,
+            max_files,
+            size,
+            k,
+            hamerly,
+            seed,
+            runs,
+            converge,
+            max_iter,
+            calc_unit,
+            color_space,
+            dist_algo,
+            weights,
+            cmc,
+            filter,
+            quad_iter,
+            overlay,
+            lookahead,
+            threads,
+            scaling,
+            start,
+            end,
+            scene_cut,
+            change_threshold,
+            frame_step,
+            max_frames,
+            audio,
+            audio_file,
+            no_subtitles,
+            strict_frame_rate,
+        } = self;
+
+        let source_config = SourceConfig {
+            url: source_url,
+            key: api_key,
+            colors: color,
+            image_type,
+            per_page,
+            resolution,
+            json_path,
+            total_path,
+        };
+
+        let crawl = CrawlConfig {
+            keyword,
+            num,
+            source,
+            source_config,
+            fill_gaps,
+            gap_frames,
+            download: DownloadConfig {
+                retries,
+                backoff,
+                rate,
+                min_bytes,
+                max_bytes,
+                min_side,
+                max_side,
+                dedup,
+                ..DownloadConfig::default()
+            },
+            resume,
+        };
+
+        let config = ProcessConfig {
+            size,
+            k,
+            hamerly,
+            seed,
+            runs,
+            converge,
+            max_iter,
+            calc_unit,
+            color_space,
+            dist_algo,
+            weights,
+            cmc,
+            filter,
+            quad_iter,
+            overlay,
+            lookahead,
+            threads,
+            scaling,
+            start,
+            end,
+            scene_cut,
+            change_threshold,
+            frame_step,
+            max_frames,
+            audio,
+            audio_file,
+            subtitles: !no_subtitles,
+            fallback_frame_rate: !strict_frame_rate,
+            dedup,
+            quality: QualityFilter {
+                min_sharpness,
+                min_contrast,
+                max_flat_share,
+                max_aspect_ratio,
+            },
+        };
+
+        let scan = LibraryConfig {
+            max_depth,
+            symlinks: match follow_symlinks {
+                true => Symlinks::Follow,
+                false => Symlinks::Skip,
+            },
+            include,
+            exclude,
+            max_files,
+            seed,
+        };
+
+        run(target, library, scan, crawl, config)
+    }
+}
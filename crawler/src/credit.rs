@@ -0,0 +1,117 @@
+use {
+    crate::{Found, Result},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        fs::{read_to_string, rename, write},
+        path::Path,
+        time::SystemTime,
+    },
+};
+
+/// The attribution of the files of a folder, by file name
+const CREDITS: &str = "credits.json";
+/// The sidecar being written, renamed over the old one once complete
+const CREDITS_TMP: &str = "credits.json.tmp";
+
+/// Where a downloaded file comes from, to credit its author
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credit {
+    pub url: String,
+    pub provider: String,
+    pub keyword: String,
+    /// An HTTP date
+    pub downloaded: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// The page of the image at the provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_url: Option<String>,
+}
+
+impl Credit {
+    /// Downloaded now
+    pub(crate) fn new(found: Found, provider: &str, keyword: &str) -> Self {
+        let Found {
+            url,
+            author,
+            license,
+            page_url,
+        } = found;
+        Self {
+            url,
+            provider: provider.into(),
+            keyword: keyword.into(),
+            downloaded: httpdate::fmt_http_date(SystemTime::now()),
+            author,
+            license,
+            page_url,
+        }
+    }
+}
+
+/// The credits of the files of a folder that are still there, none if it has no sidecar.
+/// A sidecar that cannot be parsed is an error, rather than credits lost by the next write
+pub fn read_credits(folder: &Path) -> Result<BTreeMap<String, Credit>> {
+    let text = match read_to_string(folder.join(CREDITS)) {
+        Ok(text) => text,
+        Err(_) => return Ok(BTreeMap::new()),
+    };
+    let mut credits = serde_json::from_str::<BTreeMap<String, Credit>>(&text)?;
+    credits.retain(|file, _| folder.join(file).is_file());
+    Ok(credits)
+}
+
+/// Replaces the sidecar at once, so that an interrupted write leaves the old one
+pub(crate) fn write_credits(folder: &Path, credits: &BTreeMap<String, Credit>) -> Result<()> {
+    let tmp = folder.join(CREDITS_TMP);
+    write(&tmp, serde_json::to_vec_pretty(credits)?)?;
+    rename(tmp, folder.join(CREDITS))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{read_credits, write_credits, Credit},
+        crate::Found,
+        std::{
+            collections::BTreeMap,
+            fs::{create_dir, remove_dir_all, write},
+            path::PathBuf,
+        },
+    };
+
+    #[test]
+    fn sidecar() {
+        let folder = PathBuf::from("test-credits");
+        let _ = remove_dir_all(&folder);
+        create_dir(&folder).unwrap();
+        write(folder.join("0.jpg"), b"").unwrap();
+
+        let found = Found {
+            url: "https://example.com/0.jpg".into(),
+            author: Some("Jane Doe".into()),
+            ..Default::default()
+        };
+        let credit = Credit::new(found, "Pixabay", "flower");
+        let credits = ["0.jpg", "1.jpg"]
+            .iter()
+            .map(|file| (file.to_string(), credit.clone()))
+            .collect::<BTreeMap<_, _>>();
+        write_credits(&folder, &credits).unwrap();
+        let read = read_credits(&folder);
+        write(folder.join("credits.json"), b"{").unwrap();
+        let corrupt = read_credits(&folder);
+        remove_dir_all(&folder).unwrap();
+
+        // the second file is gone
+        let read = read.unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read["0.jpg"], credit);
+        assert!(corrupt.is_err());
+        assert!(read_credits(&folder).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,409 @@
+use {
+    crate::{
+        credit::{read_credits, write_credits, Credit},
+        validate::{normalize, Download, Rejection},
+        Found, ImageSource, Result, TIMEOUT,
+    },
+    anyhow::anyhow,
+    async_std::{
+        fs::File,
+        future::timeout,
+        io::{ReadExt, WriteExt},
+        sync::Mutex as AsyncMutex,
+        task::{sleep, spawn_blocking},
+    },
+    http::{header::RETRY_AFTER, HeaderMap, StatusCode},
+    isahc::HttpClient,
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        fs::{read_to_string, OpenOptions},
+        io::Write,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant, SystemTime},
+    },
+};
+
+/// Lists the downloaded files of a folder, one `file\turl\thash` per line
+const JOURNAL: &str = ".journal";
+/// Stands in the journal for the file of a rejected url
+const REJECTED: &str = "-";
+
+/// How the failed downloads are retried, how often a host is requested and which images are kept
+#[derive(Debug, Clone)]
+pub struct DownloadConfig {
+    /// The number of retries after the first attempt
+    pub retries: u32,
+    /// The delay before the first retry, doubled by each one
+    pub backoff: Duration,
+    /// The longest delay, also caps the one asked for by `Retry-After`
+    pub max_backoff: Duration,
+    /// The number of requests per second to the same host, unlimited if none
+    pub rate: Option<f32>,
+    pub min_bytes: usize,
+    pub max_bytes: usize,
+    /// The shortest side of the images, in pixels
+    pub min_side: u32,
+    /// The longest side of the images, in pixels
+    pub max_side: u32,
+    /// The Hamming distance of the perceptual hashes of two images of a folder up to which
+    /// the later one is rejected as a near duplicate
+    pub dedup: Option<u32>,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            rate: None,
+            min_bytes: 0,
+            max_bytes: 20 << 20,
+            min_side: 16,
+            max_side: 8192,
+            dedup: None,
+        }
+    }
+}
+
+/// Parses the seconds of the backoff, which cannot be negative or not finite
+pub fn str2backoff(backoff: &str) -> std::result::Result<Duration, String> {
+    match backoff.parse::<f32>() {
+        Ok(secs) if secs.is_finite() && (0. ..=u32::MAX as f32).contains(&secs) => {
+            Ok(Duration::from_secs_f32(secs))
+        }
+        _ => Err("incorrect backoff".into()),
+    }
+}
+
+/// Spaces out the requests to each host, shared by all the downloads
+#[derive(Debug)]
+pub struct Limiter {
+    config: DownloadConfig,
+    /// The earliest time of the next request to each host
+    hosts: Mutex<HashMap<String, Instant>>,
+}
+
+impl Limiter {
+    #[inline(always)]
+    pub fn new(config: DownloadConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Waits for the turn of the host
+    async fn wait(&self, host: &str) {
+        let now = Instant::now();
+        let slot = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let next = hosts.entry(host.into()).or_insert(now);
+            let slot = (*next).max(now);
+            *next = slot + self.interval();
+            slot
+        };
+        if slot > now {
+            sleep(slot - now).await;
+        }
+    }
+
+    /// Holds the requests to the host back until `until`
+    fn pause(&self, host: &str, until: Instant) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let next = hosts.entry(host.into()).or_insert(until);
+        *next = (*next).max(until);
+    }
+
+    #[inline(always)]
+    fn interval(&self) -> Duration {
+        match self.config.rate {
+            Some(rate) if rate > 0. => Duration::from_secs_f32(1. / rate),
+            _ => Duration::ZERO,
+        }
+    }
+
+    #[inline(always)]
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.config
+            .backoff
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.config.max_backoff)
+    }
+}
+
+/// What a folder holds already, read from its journal
+#[derive(Debug, Default)]
+pub(crate) struct Journal {
+    /// The urls whose files are still there
+    pub(crate) done: HashSet<String>,
+    /// The urls whose downloads were rejected
+    pub(crate) rejected: HashSet<String>,
+    /// The next free index of the file names
+    pub(crate) next: usize,
+    /// The perceptual hashes of the files that are still there
+    pub(crate) hashes: Vec<u64>,
+}
+
+impl Journal {
+    pub(crate) fn load(folder: &Path) -> Self {
+        let mut journal = Self::default();
+        for line in read_to_string(folder.join(JOURNAL))
+            .unwrap_or_default()
+            .lines()
+        {
+            let mut fields = line.split('\t');
+            let (file, url) = match (fields.next(), fields.next()) {
+                (Some(file), Some(url)) => (file, url),
+                _ => continue,
+            };
+            if file == REJECTED {
+                journal.rejected.insert(url.to_owned());
+                continue;
+            }
+            if let Some(idx) = file
+                .split('.')
+                .next()
+                .and_then(|idx| idx.parse::<usize>().ok())
+            {
+                journal.next = journal.next.max(idx + 1);
+            }
+            // the file may have been deleted since
+            if folder.join(file).is_file() {
+                journal.done.insert(url.to_owned());
+                if let Some(hash) = fields
+                    .next()
+                    .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+                {
+                    journal.hashes.push(hash);
+                }
+            }
+        }
+        journal
+    }
+}
+
+/// The folder the downloads of a keyword are saved into
+#[derive(Debug)]
+pub(crate) struct Folder {
+    pub(crate) path: PathBuf,
+    pub(crate) provider: String,
+    pub(crate) keyword: String,
+    /// The perceptual hashes of the images
+    pub(crate) hashes: Mutex<Vec<u64>>,
+    /// Rewritten to the sidecar by every download, locked until it is written
+    pub(crate) credits: AsyncMutex<BTreeMap<String, Credit>>,
+}
+
+impl Folder {
+    /// With the `hashes` of its journal
+    pub(crate) fn new(
+        path: PathBuf,
+        provider: &str,
+        keyword: &str,
+        hashes: Vec<u64>,
+    ) -> Result<Self> {
+        let credits = read_credits(&path)?;
+        Ok(Self {
+            path,
+            provider: provider.into(),
+            keyword: keyword.into(),
+            hashes: Mutex::new(hashes),
+            credits: AsyncMutex::new(credits),
+        })
+    }
+
+    /// The sidecars are written one after the other, in the order of the credits
+    async fn credit(&self, file: String, found: Found) -> Result<()> {
+        let mut credits = self.credits.lock().await;
+        credits.insert(file, Credit::new(found, &self.provider, &self.keyword));
+        let path = self.path.clone();
+        let snapshot = credits.clone();
+        spawn_blocking(move || write_credits(&path, &snapshot)).await
+    }
+}
+
+fn record(folder: &Path, file: &str, url: &str, hash: u64) -> Result<()> {
+    append(folder, &format!("{}\t{}\t{:016x}\n", file, url, hash))
+}
+
+/// Journaled, so that resuming does not download the url again
+fn reject(folder: &Path, url: &str, rejection: Rejection) -> Result<Download> {
+    append(folder, &format!("{}\t{}\n", REJECTED, url))?;
+    Ok(Download::Rejected(rejection))
+}
+
+fn append(folder: &Path, line: &str) -> Result<()> {
+    let mut journal = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(folder.join(JOURNAL))?;
+    // a single write, the downloads append concurrently
+    journal.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Throttled, then too busy or failing
+#[inline(always)]
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+}
+
+/// Either a number of seconds or a date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .ok(),
+    }
+}
+
+/// Retries the connection failures, the timeouts and the throttled or failing responses
+/// with exponential backoff. The files are decoded before they are saved, compared with the
+/// images of the folder and credited in its sidecar
+pub(crate) async fn download_url(
+    client: Arc<HttpClient>,
+    source: Arc<dyn ImageSource>,
+    limiter: Arc<Limiter>,
+    folder: Arc<Folder>,
+    found: Found,
+    idx: usize,
+) -> Result<Download> {
+    let url = found.url.clone();
+    let mut attempt = 0;
+    loop {
+        let request = source.download(&url)?;
+        let host = request.uri().host().unwrap_or_default().to_owned();
+        limiter.wait(&host).await;
+
+        let err = match client.send_async(request).await {
+            Ok(mut res) if res.status().is_success() => {
+                let config = limiter.config.clone();
+                match res.body().len() {
+                    Some(len) if len > config.max_bytes as u64 => {
+                        return reject(&folder.path, &url, Rejection::Bytes(len as usize));
+                    }
+                    _ => (),
+                }
+                // the length may be unknown, so no more than one byte past the limit is read
+                let mut bytes = Vec::new();
+                let mut body = res.body_mut().take(config.max_bytes as u64 + 1);
+                match timeout(TIMEOUT, body.read_to_end(&mut bytes)).await {
+                    Ok(Ok(_)) => {
+                        let dedup = config.dedup;
+                        let img = match spawn_blocking(move || normalize(bytes, &config)).await {
+                            Ok(img) => img,
+                            Err(rejection) => return reject(&folder.path, &url, rejection),
+                        };
+                        if let Some(threshold) = dedup {
+                            let mut hashes = folder.hashes.lock().unwrap();
+                            if let Some(distance) = hashes
+                                .iter()
+                                .map(|hash| (img.hash ^ hash).count_ones())
+                                .filter(|distance| *distance <= threshold)
+                                .min()
+                            {
+                                let rejection = Rejection::Duplicate(distance);
+                                drop(hashes);
+                                return reject(&folder.path, &url, rejection);
+                            }
+                            hashes.push(img.hash);
+                        }
+                        let name = format!("{}.{}", idx, img.ext);
+                        let mut file = File::create(folder.path.join(&name)).await?;
+                        file.write_all(&img.bytes).await?;
+                        record(&folder.path, &name, &url, img.hash)?;
+                        folder.credit(name, found).await?;
+                        return Ok(Download::Saved);
+                    }
+                    Ok(Err(err)) => anyhow!(err),
+                    Err(err) => anyhow!(err),
+                }
+            }
+            Ok(res) => {
+                let status = res.status();
+                if !retryable(status) {
+                    return Err(anyhow!("{} responded {}", url, status));
+                }
+                if let Some(delay) = retry_after(res.headers()) {
+                    let delay = delay.min(limiter.config.max_backoff);
+                    limiter.pause(&host, Instant::now() + delay);
+                }
+                anyhow!("{} responded {}", url, status)
+            }
+            Err(err) => err.into(),
+        };
+
+        if attempt >= limiter.config.retries {
+            return Err(err);
+        }
+        sleep(limiter.backoff(attempt)).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{retry_after, DownloadConfig, Limiter},
+        async_std::task::block_on,
+        http::{header::RETRY_AFTER, HeaderMap, HeaderValue},
+        std::time::{Duration, Instant, SystemTime},
+    };
+
+    #[test]
+    fn parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(100));
+        headers.insert(RETRY_AFTER, date.parse().unwrap());
+        let delay = retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(90) && delay <= Duration::from_secs(100));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn limiter() {
+        let limiter = Limiter::new(DownloadConfig {
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(250),
+            rate: Some(20.),
+            ..Default::default()
+        });
+        assert_eq!(limiter.backoff(0), Duration::from_millis(100));
+        assert_eq!(limiter.backoff(1), Duration::from_millis(200));
+        assert_eq!(limiter.backoff(5), Duration::from_millis(250));
+
+        let now = Instant::now();
+        block_on(async {
+            for _ in 0..3 {
+                limiter.wait("a").await;
+            }
+        });
+        assert!(now.elapsed() >= Duration::from_millis(100));
+
+        // the other hosts have their own turns
+        let limiter = Limiter::new(DownloadConfig {
+            rate: Some(1.),
+            ..Default::default()
+        });
+        block_on(async {
+            limiter.wait("a").await;
+            limiter.wait("b").await;
+        });
+        let hosts = limiter.hosts.lock().unwrap();
+        assert!(hosts["b"] < hosts["a"] + limiter.interval() / 2);
+    }
+}
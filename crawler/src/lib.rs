@@ -1,193 +1,167 @@
-use {
-    async_std::{
-        fs::File,
-        future::timeout,
-        io::WriteExt,
-        task::{spawn, JoinHandle},
+mod credit;
+mod download;
+mod source;
+mod validate;
+
+pub use {
+    anyhow::Result,
+    credit::{read_credits, Credit},
+    download::{str2backoff, DownloadConfig, Limiter},
+    isahc::HttpClient,
+    source::{
+        str2color, str2image_type, str2resolution, str2source, Baidu, Found, ImageSource,
+        ImageType, JsonEndpoint, JsonPath, Manifest, Page, Pixabay, Resolution, Source,
+        SourceConfig, COLORS,
     },
-    futures::stream::{futures_unordered, FuturesUnordered},
-    http::{Method, Uri},
-    isahc::{config::VersionNegotiation, prelude::*, Request},
-    mime::Mime,
-    serde::Deserialize,
-    std::{borrow::Cow, path::PathBuf, sync::Arc, time::Duration},
-    urlencoding::encode,
+    validate::{Download, Rejection},
 };
 
-pub use {anyhow::Result, isahc::HttpClient};
+use {
+    async_std::task::{spawn, JoinHandle},
+    download::{download_url, Folder, Journal},
+    futures::stream::{futures_unordered, FuturesUnordered},
+    isahc::prelude::*,
+    std::{path::PathBuf, sync::Arc, time::Duration},
+};
 
-type Params = [(&'static str, Cow<'static, str>); 10];
 type Task<T> = JoinHandle<T>;
 type Tasks<T> = FuturesUnordered<Task<T>>;
 pub type TasksIter<T> = futures_unordered::IntoIter<Task<T>>;
 
-const BASE_URL: &str = "https://image.baidu.com/search/acjson";
-const PAGE_NUM: usize = 50;
 const TIMEOUT: Duration = Duration::from_secs(60);
 const CONCURRENT: usize = 24;
-const BASE_PARAMS: Params = [
-    ("queryWord", Cow::Borrowed("")),
-    ("word", Cow::Borrowed("")),
-    ("pn", Cow::Borrowed("0")),
-    ("face", Cow::Borrowed("0")),
-    ("ie", Cow::Borrowed("utf-8")),
-    ("ipn", Cow::Borrowed("rj")),
-    ("oe", Cow::Borrowed("utf-8")),
-    ("pn", Cow::Borrowed("0")),
-    ("rn", Cow::Borrowed("50")),
-    ("tn", Cow::Borrowed("resultjson_com")),
-];
 
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) \
 Chrome/96.0.4664.45 Safari/537.36";
-const HEADERS: [(&str, &str); 6] = [
+const HEADERS: [(&str, &str); 4] = [
     ("Accept", "image/jpeg, image/png, */*; q=0.9"),
     ("Accept-Encoding", "gzip, deflate, br"),
     ("Accept-Language", "zh-CN,zh;q=0.9"),
-    ("Host", "image.baidu.com"),
-    ("Referer", "https://image.baidu.com/"),
     ("User-Agent", USER_AGENT),
 ];
 
-#[allow(non_snake_case)]
-#[derive(Debug, Deserialize)]
-struct ImgData {
-    thumbURL: Option<String>,
-}
-
-#[allow(non_snake_case)]
-#[derive(Debug, Deserialize)]
-struct Res {
-    displayNum: usize,
-    data: Vec<ImgData>,
-}
-
+/// Downloads the images found for the keyword into the folder, the ones listed in its journal
+/// by an earlier crawl are saved or rejected already. The saved files are credited in the `credits.json`
+/// of the folder, whose sidecar must parse if there is one
 pub fn download_urls(
     client: Arc<HttpClient>,
-    urls: Vec<String>,
-    folder: PathBuf,
-) -> Tasks<Result<bool>> {
-    urls.into_iter()
-        .enumerate()
-        .map(|(idx, url)| spawn(download_url(client.clone(), url, folder.clone(), idx)))
-        .collect::<FuturesUnordered<_>>()
-}
-
-async fn download_url(
-    client: Arc<HttpClient>,
-    url: String,
+    source: Arc<dyn ImageSource>,
+    limiter: Arc<Limiter>,
+    keyword: &str,
+    images: Vec<Found>,
     folder: PathBuf,
-    idx: usize,
-) -> Result<bool> {
-    let url = url.parse::<Uri>().unwrap();
-    let request = Request::builder()
-        .method(Method::GET)
-        .header("Host", url.host().unwrap())
-        .uri(url)
-        .version_negotiation(VersionNegotiation::http2())
-        .body(())
-        .unwrap();
-
-    let mut res = client.send_async(request).await?;
-    if let Some(typ) = res.headers().get("content-type") {
-        let typ = typ.to_str().unwrap().parse::<Mime>().unwrap();
-        let ext = typ.subtype().as_str();
-        let bytes = timeout(TIMEOUT, res.bytes()).await??;
-        let mut file = File::create(folder.join(format!("{}.{}", idx, ext)))
-            .await
-            .unwrap();
-        file.write(&bytes).await.unwrap();
-        return Ok(true);
-    }
-    Ok(false)
+) -> Result<Tasks<Result<Download>>> {
+    let Journal {
+        done,
+        rejected,
+        next: mut idx,
+        hashes,
+    } = Journal::load(&folder);
+    let folder = Arc::new(Folder::new(folder, source.name(), keyword, hashes)?);
+    let tasks = images
+        .into_iter()
+        .map(|found| {
+            if done.contains(&found.url) {
+                return spawn(async { Result::<_>::Ok(Download::Saved) });
+            }
+            if rejected.contains(&found.url) {
+                let rejection = Rejection::Earlier;
+                return spawn(async { Result::<_>::Ok(Download::Rejected(rejection)) });
+            }
+            idx += 1;
+            spawn(download_url(
+                client.clone(),
+                source.clone(),
+                limiter.clone(),
+                folder.clone(),
+                found,
+                idx - 1,
+            ))
+        })
+        .collect::<FuturesUnordered<_>>();
+    Ok(tasks)
 }
 
+/// Searches the first page for the number of results, the other pages are requested in parallel.
+/// At most `num` images are returned
 pub async fn get_urls(
     client: Arc<HttpClient>,
+    source: Arc<dyn ImageSource>,
     keyword: String,
     num: usize,
-) -> Result<(usize, Tasks<Result<Vec<String>>>)> {
-    let keyword = encode(&keyword).to_string();
-    let mut params = BASE_PARAMS.clone();
-    params[0].1 = keyword.clone().into();
-    params[1].1 = keyword.into();
-    let url = parse_url(&params);
-
-    let mut res = client.get_async(url).await?;
-    let Res { displayNum, .. } = res.json::<Res>().await?;
-    let num = num.min(displayNum);
-
-    let tasks = (0..num)
-        .step_by(PAGE_NUM)
-        .map(|start| {
-            let client = client.clone();
-            spawn(get_part_urls(client, start.to_string(), params.clone()))
-        })
-        .collect::<FuturesUnordered<_>>();
+) -> Result<(usize, Tasks<Result<Vec<Found>>>)> {
+    let Page { total, mut images } = source.search(client.clone(), keyword.clone(), 0).await?;
+    let num = num.min(total);
+    let page_size = source.page_size().max(1);
+    images.truncate(num);
 
-    Ok((num, tasks))
-}
-
-async fn get_part_urls(
-    client: Arc<HttpClient>,
-    start: String,
-    mut params: Params,
-) -> Result<Vec<String>> {
-    params[2].1 = start.into();
-    let url = parse_url(&params);
-    let mut res = client.get_async(url).await?;
-    let Res { data, .. } = res.json::<Res>().await?;
-
-    let mut ret = Vec::with_capacity(data.len());
-    for ImgData { thumbURL } in data {
-        if let Some(str) = thumbURL {
-            ret.push(str);
-        }
+    let tasks = FuturesUnordered::new();
+    tasks.push(spawn(async move { Result::<_>::Ok(images) }));
+    for start in (page_size..num).step_by(page_size) {
+        let page = source.search(client.clone(), keyword.clone(), start);
+        tasks.push(spawn(async move {
+            let mut images = page.await?.images;
+            images.truncate(num - start);
+            Result::<_>::Ok(images)
+        }));
     }
-    Ok(ret)
-}
 
-fn parse_url(params: &Params) -> Uri {
-    format!(
-        "{}?{}",
-        BASE_URL,
-        params
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect::<Vec<_>>()
-            .join("&")
-    )
-    .parse::<Uri>()
-    .unwrap()
+    Ok((num, tasks))
 }
 
-pub fn gen_client() -> Arc<HttpClient> {
-    Arc::new(
+pub fn gen_client() -> Result<Arc<HttpClient>> {
+    Ok(Arc::new(
         HttpClient::builder()
             .timeout(TIMEOUT)
             .max_connections(CONCURRENT)
             .default_headers(&HEADERS)
-            .build()
-            .unwrap(),
-    )
+            .build()?,
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use {
-        super::PAGE_NUM,
+        super::{
+            read_credits, str2backoff, Download, DownloadConfig, Found, ImageSource, Limiter,
+            Rejection, Source, SourceConfig,
+        },
         async_std::task::block_on,
+        image::{DynamicImage, ImageOutputFormat},
         isahc::HttpClient,
         std::{
-            fs::{create_dir, remove_dir_all},
+            fs::{create_dir, read_dir, remove_dir_all, remove_file, write},
+            io::{Read, Write},
+            net::TcpListener,
             path::PathBuf,
-            sync::Arc,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            thread,
+            time::Duration,
         },
     };
 
-    fn get_urls(client: Arc<HttpClient>) -> Vec<String> {
+    /// The status line, the extra headers, the content type and the body of a response
+    type Reply = (&'static str, String, &'static str, Vec<u8>);
+
+    fn png(size: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        DynamicImage::new_rgb8(size, size)
+            .write_to(&mut buf, ImageOutputFormat::Png)
+            .unwrap();
+        buf
+    }
+
+    fn get_urls(
+        client: Arc<HttpClient>,
+        source: Arc<dyn ImageSource>,
+        keyword: &str,
+        num: usize,
+    ) -> Vec<Found> {
         block_on(async {
-            let (num, tasks) = super::get_urls(client, "风景".into(), PAGE_NUM * 2)
+            let (num, tasks) = super::get_urls(client, source, keyword.into(), num)
                 .await
                 .unwrap();
             let mut urls = Vec::with_capacity(num);
@@ -200,18 +174,479 @@ mod tests {
         })
     }
 
-    #[test]
-    fn download_urls() {
-        let client = super::gen_client();
-        let urls = get_urls(client.clone());
+    fn download_urls(
+        client: Arc<HttpClient>,
+        source: Arc<dyn ImageSource>,
+        urls: Vec<Found>,
+        keyword: &str,
+    ) -> PathBuf {
         block_on(async {
-            let folder = PathBuf::from("test");
+            let folder = PathBuf::from(keyword);
             let _ = remove_dir_all(&folder);
             create_dir(&folder).unwrap();
-            let tasks = super::download_urls(client, urls, folder);
+            let limiter = Limiter::new(DownloadConfig::default());
+            let tasks =
+                super::download_urls(client, source, limiter, keyword, urls, folder.clone())
+                    .unwrap();
             for task in tasks {
                 let _ = task.await;
             }
+            folder
+        })
+    }
+
+    /// Answers every request with the reply of the handler to the path, the handler is made
+    /// with the base url of the server
+    fn serve_with<F>(handler: impl FnOnce(&str) -> F) -> String
+    where
+        F: FnMut(&str) -> Reply + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let mut handler = handler(&base);
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buf = [0; 4096];
+                let len = stream.read(&mut buf).unwrap_or_default();
+                let req = String::from_utf8_lossy(&buf[..len]);
+                let path = req.split_whitespace().nth(1).unwrap_or_default();
+                let (status, headers, typ, body) = handler(path);
+                let head = format!(
+                    "HTTP/1.1 {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    headers,
+                    typ,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&body);
+            }
         });
+        base
+    }
+
+    /// Answers every request with the first response whose prefix matches the path
+    fn serve(responses: impl Fn(&str) -> Vec<(String, &'static str, Vec<u8>)>) -> String {
+        serve_with(|base| {
+            let responses = responses(base);
+            move |path: &str| match responses
+                .iter()
+                .find(|(prefix, ..)| path.starts_with(prefix.as_str()))
+            {
+                Some((_, typ, body)) => ("200 OK", String::new(), *typ, body.clone()),
+                None => ("404 Not Found", String::new(), "text/plain", Vec::new()),
+            }
+        })
+    }
+
+    #[test]
+    fn mock_source() {
+        let base = serve(|base| {
+            let json = format!(
+                r#"{{"displayNum":3,"data":[{{"thumbURL":"{0}/img/0"}},{{"thumbURL":"{0}/img/1"}},{{}}]}}"#,
+                base
+            );
+            vec![
+                ("/search".into(), "application/json", json.into_bytes()),
+                ("/img/".into(), "image/png", png(32)),
+            ]
+        });
+        let client = super::gen_client().unwrap();
+        let config = SourceConfig {
+            url: Some(format!("{}/search", base)),
+            ..Default::default()
+        };
+        let source = Source::Baidu.build(&config).unwrap();
+
+        let urls = get_urls(client.clone(), source.clone(), "风景", 2);
+        assert_eq!(
+            urls,
+            [
+                Found::from(format!("{}/img/0", base)),
+                Found::from(format!("{}/img/1", base))
+            ]
+        );
+
+        let folder = download_urls(client, source, urls, "test-mock");
+        let mut files = read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, [".journal", "0.png", "1.png", "credits.json"]);
+        remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn mock_pixabay() {
+        let base = serve(|base| {
+            let hit = |id| {
+                format!(
+                    r#"{{"pageURL":"{0}/photos/{1}","previewURL":"{0}/img/{1}_150.jpg","webformatURL":"{0}/img/{1}_640.jpg","largeImageURL":"{0}/img/{1}_1280.jpg","user":"user{1}"}}"#,
+                    base, id
+                )
+            };
+            let json = format!(
+                r#"{{"total":9,"totalHits":2,"hits":[{},{}]}}"#,
+                hit(0),
+                hit(1)
+            );
+            vec![
+                // only answers with the key
+                (
+                    "/api/?key=secret&q=".into(),
+                    "application/json",
+                    json.into_bytes(),
+                ),
+                ("/img/".into(), "image/jpeg", png(32)),
+            ]
+        });
+        let client = super::gen_client().unwrap();
+        let config = SourceConfig {
+            url: Some(format!("{}/api/", base)),
+            key: Some("secret".into()),
+            colors: vec!["red".into(), "blue".into()],
+            ..Default::default()
+        };
+        let source = Source::Pixabay.build(&config).unwrap();
+
+        let urls = get_urls(client.clone(), source.clone(), "flower", 100);
+        let expected = (0..2)
+            .map(|id| Found {
+                url: format!("{}/img/{}_340.jpg", base, id),
+                author: Some(format!("user{}", id)),
+                license: Some("Pixabay Content License".into()),
+                page_url: Some(format!("{}/photos/{}", base, id)),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(urls, expected);
+
+        let folder = download_urls(client, source, urls, "test-pixabay");
+        // the images, the journal and the credits
+        assert_eq!(read_dir(&folder).unwrap().count(), 4);
+        let credits = read_credits(&folder).unwrap();
+        remove_dir_all(folder).unwrap();
+
+        assert_eq!(credits.len(), 2);
+        for credit in credits.values() {
+            assert_eq!(
+                (credit.provider.as_str(), credit.keyword.as_str()),
+                ("Pixabay", "test-pixabay")
+            );
+            let found = expected
+                .iter()
+                .find(|found| found.url == credit.url)
+                .unwrap();
+            assert_eq!(credit.author, found.author);
+            assert_eq!(credit.page_url, found.page_url);
+        }
+    }
+
+    #[test]
+    fn mock_json_endpoint() {
+        let base = serve(|base| {
+            let page = |offset: usize| {
+                format!(
+                    r#"{{"meta":{{"count":"3"}},"items":[{{"file":{{"src":"{0}/img/{1}"}}}},{{"file":{{"src":"{0}/img/{2}"}}}}]}}"#,
+                    base,
+                    offset,
+                    offset + 1
+                )
+            };
+            vec![
+                (
+                    "/search?q=sea&offset=0&".into(),
+                    "application/json",
+                    page(0).into_bytes(),
+                ),
+                (
+                    "/search?q=sea&offset=2&".into(),
+                    "application/json",
+                    page(2).into_bytes(),
+                ),
+                ("/img/".into(), "image/png", png(32)),
+            ]
+        });
+        let client = super::gen_client().unwrap();
+        let config = SourceConfig {
+            url: Some(format!(
+                "{}/search?q={{keyword}}&offset={{offset}}&limit={{per_page}}",
+                base
+            )),
+            per_page: 2,
+            json_path: Some("$.items[*].file.src".into()),
+            total_path: Some("$.meta.count".into()),
+            ..Default::default()
+        };
+        let source = Source::Json.build(&config).unwrap();
+
+        let mut urls = get_urls(client, source, "sea", 10)
+            .into_iter()
+            .map(|found| found.url)
+            .collect::<Vec<_>>();
+        urls.sort();
+        let expected = (0..3)
+            .map(|i| format!("{}/img/{}", base, i))
+            .collect::<Vec<_>>();
+        assert_eq!(urls, expected);
+    }
+
+    #[test]
+    fn mock_retry() {
+        let busy = Arc::new(AtomicUsize::new(0));
+        let missing = Arc::new(AtomicUsize::new(0));
+        let base = {
+            let busy = busy.clone();
+            let missing = missing.clone();
+            serve_with(move |_| {
+                move |path: &str| match path {
+                    // throttled once
+                    "/busy" if busy.fetch_add(1, Ordering::SeqCst) == 0 => (
+                        "429 Too Many Requests",
+                        "Retry-After: 0\r\n".into(),
+                        "text/plain",
+                        Vec::new(),
+                    ),
+                    "/missing" => {
+                        missing.fetch_add(1, Ordering::SeqCst);
+                        ("404 Not Found", String::new(), "text/plain", Vec::new())
+                    }
+                    _ => ("200 OK", String::new(), "image/png", png(32)),
+                }
+            })
+        };
+        let client = super::gen_client().unwrap();
+        let source = Source::Baidu.build(&SourceConfig::default()).unwrap();
+        let limiter = Limiter::new(DownloadConfig {
+            backoff: Duration::from_millis(10),
+            ..Default::default()
+        });
+
+        let folder = PathBuf::from("test-retry");
+        let _ = remove_dir_all(&folder);
+        create_dir(&folder).unwrap();
+        let urls = vec![
+            format!("{}/busy", base).into(),
+            format!("{}/missing", base).into(),
+        ];
+        let results = block_on(async {
+            let mut results = Vec::new();
+            let tasks =
+                super::download_urls(client, source, limiter, "retry", urls, folder.clone())
+                    .unwrap();
+            for task in tasks {
+                results.push(task.await.is_ok());
+            }
+            results
+        });
+        remove_dir_all(folder).unwrap();
+
+        assert_eq!(results.iter().filter(|ok| **ok).count(), 1);
+        assert_eq!(busy.load(Ordering::SeqCst), 2);
+        // the client errors are not retried
+        assert_eq!(missing.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn mock_resume() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let base = {
+            let requests = requests.clone();
+            serve_with(move |_| {
+                move |_: &str| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    ("200 OK", String::new(), "image/png", png(32))
+                }
+            })
+        };
+        let client = super::gen_client().unwrap();
+        let source = Source::Baidu.build(&SourceConfig::default()).unwrap();
+        let urls = (0..3)
+            .map(|i| Found::from(format!("{}/img/{}", base, i)))
+            .collect::<Vec<_>>();
+
+        let folder = download_urls(
+            client.clone(),
+            source.clone(),
+            urls[..2].to_vec(),
+            "test-resume",
+        );
+        // interrupted before the second one was written
+        remove_file(folder.join("1.png")).unwrap();
+        block_on(async {
+            let limiter = Limiter::new(DownloadConfig::default());
+            let tasks =
+                super::download_urls(client, source, limiter, "test-resume", urls, folder.clone())
+                    .unwrap();
+            for task in tasks {
+                assert_eq!(task.await.unwrap(), Download::Saved);
+            }
+        });
+        let mut files = read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        let credits = read_credits(&folder).unwrap();
+        remove_dir_all(folder).unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            files,
+            [".journal", "0.png", "2.png", "3.png", "credits.json"]
+        );
+        assert_eq!(
+            credits.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["0.png", "2.png", "3.png"]
+        );
+    }
+
+    #[test]
+    fn mock_rejections() {
+        let base = serve(|_| {
+            let mut gif = Vec::new();
+            DynamicImage::new_rgb8(32, 32)
+                .write_to(&mut gif, ImageOutputFormat::Gif)
+                .unwrap();
+            vec![
+                ("/page".into(), "image/jpeg", b"<html></html>".to_vec()),
+                ("/small".into(), "image/png", png(4)),
+                ("/gif".into(), "image/gif", gif),
+            ]
+        });
+        let client = super::gen_client().unwrap();
+        let source = Source::Baidu.build(&SourceConfig::default()).unwrap();
+        let urls = ["page", "small", "gif"]
+            .iter()
+            .map(|path| Found::from(format!("{}/{}", base, path)))
+            .collect::<Vec<_>>();
+
+        let folder = PathBuf::from("test-rejections");
+        let _ = remove_dir_all(&folder);
+        create_dir(&folder).unwrap();
+        let crawl = || {
+            let (client, source, urls) = (client.clone(), source.clone(), urls.clone());
+            let mut results = block_on(async {
+                let limiter = Limiter::new(DownloadConfig::default());
+                let mut results = Vec::new();
+                let tasks = super::download_urls(
+                    client,
+                    source,
+                    limiter,
+                    "rejections",
+                    urls,
+                    folder.clone(),
+                )
+                .unwrap();
+                for task in tasks {
+                    results.push(task.await.unwrap());
+                }
+                results
+            });
+            results.sort_by_key(|download| format!("{:?}", download));
+            results
+        };
+        let results = crawl();
+        // resumed, the rejected urls are not downloaded again
+        let resumed = crawl();
+        let converted = image::open(folder.join("2.png")).map(|img| img.to_rgb8().dimensions());
+        remove_dir_all(&folder).unwrap();
+
+        assert!(matches!(
+            results.as_slice(),
+            [
+                Download::Rejected(Rejection::Dimensions(4, 4)),
+                Download::Rejected(Rejection::Undecodable(_)),
+                Download::Saved,
+            ]
+        ));
+        assert_eq!(
+            resumed,
+            [
+                Download::Rejected(Rejection::Earlier),
+                Download::Rejected(Rejection::Earlier),
+                Download::Saved,
+            ]
+        );
+        assert_eq!(converted.unwrap(), (32, 32));
+    }
+
+    #[test]
+    fn mock_duplicates() {
+        let base = serve(|_| {
+            vec![
+                ("/big".into(), "image/png", png(64)),
+                ("/small".into(), "image/png", png(32)),
+            ]
+        });
+        let client = super::gen_client().unwrap();
+        let source = Source::Baidu.build(&SourceConfig::default()).unwrap();
+        let urls = vec![
+            format!("{}/big", base).into(),
+            format!("{}/small", base).into(),
+        ];
+
+        let folder = PathBuf::from("test-duplicates");
+        let _ = remove_dir_all(&folder);
+        create_dir(&folder).unwrap();
+        let results = block_on(async {
+            let limiter = Limiter::new(DownloadConfig {
+                dedup: Some(4),
+                ..Default::default()
+            });
+            let mut results = Vec::new();
+            let tasks =
+                super::download_urls(client, source, limiter, "duplicates", urls, folder.clone())
+                    .unwrap();
+            for task in tasks {
+                results.push(task.await.unwrap());
+            }
+            results
+        });
+        remove_dir_all(folder).unwrap();
+
+        assert!(results.contains(&Download::Saved));
+        assert!(results.contains(&Download::Rejected(Rejection::Duplicate(0))));
+    }
+
+    #[test]
+    fn manifest() {
+        let path = PathBuf::from("test-manifest.csv");
+        write(
+            &path,
+            "url\nhttps://example.com/a.jpg\nhttps://example.com/b.jpg\n",
+        )
+        .unwrap();
+        let config = SourceConfig {
+            url: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let source = Source::Manifest.build(&config).unwrap();
+        let urls = get_urls(super::gen_client().unwrap(), source, "client", 1);
+        remove_file(path).unwrap();
+        assert_eq!(urls, [Found::from("https://example.com/a.jpg".to_owned())]);
+    }
+
+    #[test]
+    fn download_urls_baidu() {
+        let client = super::gen_client().unwrap();
+        let source = Source::Baidu.build(&SourceConfig::default()).unwrap();
+        let urls = get_urls(
+            client.clone(),
+            source.clone(),
+            "风景",
+            source.page_size() * 2,
+        );
+        download_urls(client, source, urls, "test");
+    }
+
+    #[test]
+    fn backoff() {
+        assert_eq!(str2backoff("0.5"), Ok(Duration::from_millis(500)));
+        assert_eq!(str2backoff("0"), Ok(Duration::ZERO));
+        // Duration::from_secs_f32 panics on these
+        for backoff in ["-1", "NaN", "inf", "1e30"] {
+            assert!(str2backoff(backoff).is_err());
+        }
     }
 }
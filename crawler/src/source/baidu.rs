@@ -0,0 +1,96 @@
+use {
+    super::{parse_url, Found, ImageSource, Page},
+    crate::Result,
+    futures::future::{BoxFuture, FutureExt},
+    http::Method,
+    isahc::{prelude::*, HttpClient, Request},
+    serde::Deserialize,
+    std::sync::Arc,
+    urlencoding::encode,
+};
+
+const BASE_URL: &str = "https://image.baidu.com/search/acjson";
+const REFERER: &str = "https://image.baidu.com/";
+const PAGE_NUM: usize = 50;
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize)]
+struct ImgData {
+    thumbURL: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize)]
+struct Res {
+    displayNum: usize,
+    data: Vec<ImgData>,
+}
+
+/// The JSON API behind the image search of Baidu
+#[derive(Debug, Clone)]
+pub struct Baidu {
+    base_url: String,
+}
+
+impl Baidu {
+    #[inline(always)]
+    pub fn new(base_url: Option<String>) -> Self {
+        Self {
+            base_url: base_url.unwrap_or_else(|| BASE_URL.into()),
+        }
+    }
+
+    fn params(keyword: &str, start: usize) -> [(&'static str, String); 10] {
+        let keyword = encode(keyword).to_string();
+        [
+            ("queryWord", keyword.clone()),
+            ("word", keyword),
+            ("pn", start.to_string()),
+            ("face", "0".into()),
+            ("ie", "utf-8".into()),
+            ("ipn", "rj".into()),
+            ("oe", "utf-8".into()),
+            ("pn", start.to_string()),
+            ("rn", PAGE_NUM.to_string()),
+            ("tn", "resultjson_com".into()),
+        ]
+    }
+}
+
+impl ImageSource for Baidu {
+    #[inline(always)]
+    fn name(&self) -> &str {
+        "Baidu"
+    }
+
+    #[inline(always)]
+    fn page_size(&self) -> usize {
+        PAGE_NUM
+    }
+
+    fn search(
+        &self,
+        client: Arc<HttpClient>,
+        keyword: String,
+        start: usize,
+    ) -> BoxFuture<'static, Result<Page>> {
+        let url = parse_url(&self.base_url, &Self::params(&keyword, start));
+        async move {
+            let request = Request::builder()
+                .method(Method::GET)
+                .header("Referer", REFERER)
+                .uri(url?)
+                .body(())?;
+            let mut res = client.send_async(request).await?;
+            let Res { displayNum, data } = res.json::<Res>().await?;
+            Ok(Page {
+                total: displayNum,
+                images: data
+                    .into_iter()
+                    .filter_map(|ImgData { thumbURL }| thumbURL.map(Found::from))
+                    .collect(),
+            })
+        }
+        .boxed()
+    }
+}
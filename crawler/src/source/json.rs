@@ -0,0 +1,103 @@
+use {
+    super::{Found, ImageSource, JsonPath, Page, SourceConfig},
+    crate::Result,
+    anyhow::anyhow,
+    futures::future::{BoxFuture, FutureExt},
+    http::Uri,
+    isahc::{prelude::*, HttpClient},
+    serde_json::Value,
+    std::sync::Arc,
+    urlencoding::encode,
+};
+
+const PAGING: [&str; 2] = ["{page}", "{offset}"];
+
+/// Any JSON search API, described by an url template and the JSONPath to the image urls.
+/// The template may contain `{keyword}`, `{page}` (from 1), `{offset}` (from 0), `{per_page}`
+/// and `{key}`, without `{page}` or `{offset}` only the first page is requested
+#[derive(Debug, Clone)]
+pub struct JsonEndpoint {
+    template: String,
+    key: String,
+    per_page: usize,
+    urls: JsonPath,
+    /// Without it the endpoint is taken to hold as many results as requested,
+    /// the pages past its last one come back empty
+    total: Option<JsonPath>,
+}
+
+impl JsonEndpoint {
+    pub fn new(config: &SourceConfig) -> Result<Self> {
+        let template = config
+            .url
+            .clone()
+            .ok_or_else(|| anyhow!("missing the url template of the JSON endpoint"))?;
+        let urls = config
+            .json_path
+            .as_deref()
+            .ok_or_else(|| anyhow!("missing the JSONPath to the image urls"))?
+            .parse()?;
+        let total = match &config.total_path {
+            Some(path) => Some(path.parse()?),
+            None => None,
+        };
+        Ok(Self {
+            template,
+            key: config.key.clone().unwrap_or_default(),
+            per_page: config.per_page.max(1),
+            urls,
+            total,
+        })
+    }
+
+    fn url(&self, keyword: &str, start: usize) -> Result<Uri> {
+        Ok(self
+            .template
+            .replace("{keyword}", &encode(keyword))
+            .replace("{page}", &(start / self.per_page + 1).to_string())
+            .replace("{offset}", &start.to_string())
+            .replace("{per_page}", &self.per_page.to_string())
+            .replace("{key}", &encode(&self.key))
+            .parse::<Uri>()?)
+    }
+}
+
+impl ImageSource for JsonEndpoint {
+    #[inline(always)]
+    fn name(&self) -> &str {
+        "JSON endpoint"
+    }
+
+    #[inline(always)]
+    fn page_size(&self) -> usize {
+        match PAGING.iter().any(|param| self.template.contains(param)) {
+            true => self.per_page,
+            false => usize::MAX,
+        }
+    }
+
+    fn search(
+        &self,
+        client: Arc<HttpClient>,
+        keyword: String,
+        start: usize,
+    ) -> BoxFuture<'static, Result<Page>> {
+        let url = self.url(&keyword, start);
+        let Self { urls, total, .. } = self.clone();
+        async move {
+            let mut res = client.get_async(url?).await?;
+            if !res.status().is_success() {
+                return Err(anyhow!("the JSON endpoint responded {}", res.status()));
+            }
+            let value = res.json::<Value>().await?;
+            let images = urls.strings(&value).into_iter().map(Found::from).collect();
+            Ok(Page {
+                total: total
+                    .and_then(|total| total.number(&value))
+                    .unwrap_or(usize::MAX),
+                images,
+            })
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,137 @@
+use {crate::Result, anyhow::anyhow, serde_json::Value, std::str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    /// Every item of an array, or every value of an object
+    All,
+}
+
+/// A subset of JSONPath: `$`, `.key`, `['key']`, `[0]`, `[*]` and `.*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(Vec<Segment>);
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let trimmed = path.trim();
+        // the root and the dot before the first key are optional
+        let owned = match trimmed.strip_prefix('$').unwrap_or(trimmed) {
+            rest if rest.is_empty() || rest.starts_with(['.', '[']) => rest.to_owned(),
+            rest => format!(".{}", rest),
+        };
+        let mut rest = owned.as_str();
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix('.') {
+                let end = tail.find(['.', '[']).unwrap_or(tail.len());
+                let (key, tail) = tail.split_at(end);
+                segments.push(match key {
+                    "" => return Err(anyhow!("empty key in {}", path)),
+                    "*" => Segment::All,
+                    key => Segment::Key(key.into()),
+                });
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix('[') {
+                let end = tail
+                    .find(']')
+                    .ok_or_else(|| anyhow!("unclosed bracket in {}", path))?;
+                let (inner, tail) = tail.split_at(end);
+                let inner = inner.trim();
+                segments.push(if inner == "*" {
+                    Segment::All
+                } else if let Ok(idx) = inner.parse::<usize>() {
+                    Segment::Index(idx)
+                } else {
+                    let key = inner
+                        .strip_prefix('\'')
+                        .and_then(|key| key.strip_suffix('\''))
+                        .or_else(|| {
+                            inner
+                                .strip_prefix('"')
+                                .and_then(|key| key.strip_suffix('"'))
+                        })
+                        .ok_or_else(|| anyhow!("incorrect selector [{}] in {}", inner, path))?;
+                    Segment::Key(key.into())
+                });
+                rest = &tail[1..];
+            } else {
+                return Err(anyhow!("incorrect path {}", path));
+            }
+        }
+        Ok(Self(segments))
+    }
+}
+
+impl JsonPath {
+    /// The values matched by the path, in document order
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut values = vec![value];
+        for segment in &self.0 {
+            values = values
+                .into_iter()
+                .flat_map(|value| -> Vec<&Value> {
+                    match (segment, value) {
+                        (Segment::Key(key), Value::Object(map)) => {
+                            map.get(key).into_iter().collect()
+                        }
+                        (Segment::Index(idx), Value::Array(arr)) => {
+                            arr.get(*idx).into_iter().collect()
+                        }
+                        (Segment::All, Value::Array(arr)) => arr.iter().collect(),
+                        (Segment::All, Value::Object(map)) => map.values().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        values
+    }
+
+    /// The matched strings, other values are skipped
+    pub fn strings(&self, value: &Value) -> Vec<String> {
+        self.select(value)
+            .into_iter()
+            .filter_map(|value| value.as_str().map(String::from))
+            .collect()
+    }
+
+    /// The first matched number, or numeric string
+    pub fn number(&self, value: &Value) -> Option<usize> {
+        self.select(value)
+            .into_iter()
+            .find_map(|value| match value {
+                Value::Number(num) => num.as_u64().map(|num| num as usize),
+                Value::String(str) => str.trim().parse().ok(),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::JsonPath, serde_json::json};
+
+    #[test]
+    fn select() {
+        let value = json!({
+            "total": "3",
+            "results": [
+                { "urls": { "small": "a" } },
+                { "urls": { "small": "b" } },
+                { "urls": {} },
+            ],
+        });
+        let path = |path: &str| path.parse::<JsonPath>().unwrap();
+
+        assert_eq!(path("$.results[*].urls.small").strings(&value), ["a", "b"]);
+        assert_eq!(path("results[1]['urls'].small").strings(&value), ["b"]);
+        assert_eq!(path("$.results.*.urls.*").strings(&value), ["a", "b"]);
+        assert_eq!(path("$.total").number(&value), Some(3));
+        assert!(path("$.missing[0]").select(&value).is_empty());
+        assert!("$.results[".parse::<JsonPath>().is_err());
+        assert!("$..results".parse::<JsonPath>().is_err());
+    }
+}
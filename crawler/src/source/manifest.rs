@@ -0,0 +1,230 @@
+use {
+    super::{Found, ImageSource, Page, SourceConfig},
+    crate::Result,
+    anyhow::anyhow,
+    futures::future::{self, BoxFuture, FutureExt},
+    isahc::HttpClient,
+    std::{fs::read_to_string, mem::take, sync::Arc},
+};
+
+const PAGE_NUM: usize = 100;
+const SEPARATORS: [char; 3] = [',', ';', '\t'];
+
+/// A local list of image urls, one per line. Below a header the lines are the rows of a CSV or
+/// TSV, whose fields may be quoted, and the first field holding an url is taken. The columns of
+/// the header named `author` and `license` credit the images below it. Above any header a line
+/// starting with an url is taken whole, commas included. The keyword only names the folder
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    images: Arc<[Found]>,
+}
+
+impl Manifest {
+    pub fn new(config: &SourceConfig) -> Result<Self> {
+        let path = config
+            .url
+            .as_deref()
+            .ok_or_else(|| anyhow!("missing the path of the manifest"))?;
+        let text = read_to_string(path).map_err(|e| anyhow!("{}: {}", path, e))?;
+        Ok(Self {
+            images: parse(&text).into(),
+        })
+    }
+}
+
+#[inline(always)]
+fn is_url(field: &str) -> bool {
+    field.starts_with("http://") || field.starts_with("https://")
+}
+
+/// The first of the separators outside the quotes
+fn separator(line: &str) -> Option<char> {
+    let mut quoted = false;
+    line.chars().find(|&c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        !quoted && SEPARATORS.contains(&c)
+    })
+}
+
+/// Splits a row at its separator. The quotes around a field are removed, and a doubled quote
+/// within them is a quote of the field
+fn fields(line: &str) -> Vec<String> {
+    let separator = separator(line);
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            c if !quoted && Some(c) == separator => fields.push(take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+        .into_iter()
+        .map(|field| field.trim().to_string())
+        .collect()
+}
+
+fn parse(text: &str) -> Vec<Found> {
+    let (mut author, mut license) = (None, None);
+    let mut header = false;
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            // an url may hold any of the separators
+            if !header && is_url(line) && !line.contains(char::is_whitespace) {
+                return Some(Found::from(line.to_string()));
+            }
+            let fields = fields(line);
+            let url = match fields.iter().find(|field| is_url(field)) {
+                Some(url) => url.to_string(),
+                None => {
+                    header |= fields.len() > 1;
+                    let column = |name| {
+                        fields
+                            .iter()
+                            .position(|field| field.eq_ignore_ascii_case(name))
+                    };
+                    // a header crediting the images below it
+                    let columns = (column("author"), column("license"));
+                    if columns.0.is_some() || columns.1.is_some() {
+                        author = columns.0;
+                        license = columns.1;
+                    }
+                    return None;
+                }
+            };
+            let field = |idx: Option<usize>| {
+                idx.and_then(|idx| fields.get(idx))
+                    .filter(|field| !field.is_empty())
+                    .map(|field| field.to_string())
+            };
+            Some(Found {
+                url,
+                author: field(author),
+                license: field(license),
+                page_url: None,
+            })
+        })
+        .collect()
+}
+
+impl ImageSource for Manifest {
+    #[inline(always)]
+    fn name(&self) -> &str {
+        "Manifest"
+    }
+
+    #[inline(always)]
+    fn page_size(&self) -> usize {
+        PAGE_NUM
+    }
+
+    fn search(
+        &self,
+        _client: Arc<HttpClient>,
+        _keyword: String,
+        start: usize,
+    ) -> BoxFuture<'static, Result<Page>> {
+        let start = start.min(self.images.len());
+        let end = (start + PAGE_NUM).min(self.images.len());
+        future::ready(Ok(Page {
+            total: self.images.len(),
+            images: self.images[start..end].to_vec(),
+        }))
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Found;
+
+    #[test]
+    fn parse() {
+        let text = "\
+# from the client
+id,url,title,Author,License
+1,https://example.com/a.jpg,A,Jane Doe,CC BY 4.0
+2;\"http://example.com/b.png\";B
+
+no url here
+https://example.com/c.jpg
+";
+        let found = super::parse(text);
+        assert_eq!(
+            found
+                .iter()
+                .map(|found| found.url.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://example.com/a.jpg",
+                "http://example.com/b.png",
+                "https://example.com/c.jpg"
+            ]
+        );
+        assert_eq!(
+            found[0],
+            Found {
+                url: "https://example.com/a.jpg".into(),
+                author: Some("Jane Doe".into()),
+                license: Some("CC BY 4.0".into()),
+                page_url: None,
+            }
+        );
+        assert_eq!(found[1].author, None);
+    }
+
+    #[test]
+    fn url_with_comma() {
+        let text = "https://example.com/a,b.jpg\nhttps://example.com/c.jpg;w=640\n";
+        let found = super::parse(text);
+        assert_eq!(
+            found
+                .iter()
+                .map(|found| found.url.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://example.com/a,b.jpg",
+                "https://example.com/c.jpg;w=640"
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_field() {
+        let text = "\
+url,author,license
+\"https://example.com/a,b.jpg\",\"Doe, Jane\",CC0
+https://example.com/c.jpg,\"The \"\"Studio\"\"\",
+";
+        let found = super::parse(text);
+        assert_eq!(
+            found,
+            [
+                Found {
+                    url: "https://example.com/a,b.jpg".into(),
+                    author: Some("Doe, Jane".into()),
+                    license: Some("CC0".into()),
+                    page_url: None,
+                },
+                Found {
+                    url: "https://example.com/c.jpg".into(),
+                    author: Some("The \"Studio\"".into()),
+                    license: None,
+                    page_url: None,
+                },
+            ]
+        );
+    }
+}
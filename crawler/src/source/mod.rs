@@ -0,0 +1,225 @@
+mod baidu;
+mod json;
+mod json_path;
+mod manifest;
+mod pixabay;
+
+pub use {
+    baidu::Baidu,
+    json::JsonEndpoint,
+    json_path::JsonPath,
+    manifest::Manifest,
+    pixabay::{ImageType, Pixabay, Resolution, COLORS},
+};
+
+use {
+    crate::Result,
+    anyhow::anyhow,
+    futures::future::BoxFuture,
+    http::{Method, Uri},
+    isahc::{config::VersionNegotiation, prelude::*, HttpClient, Request},
+    std::{fmt::Debug, sync::Arc},
+};
+
+/// An image found by a search, with the attribution the provider returns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Found {
+    pub url: String,
+    pub author: Option<String>,
+    pub license: Option<String>,
+    /// The page of the image at the provider
+    pub page_url: Option<String>,
+}
+
+impl From<String> for Found {
+    #[inline(always)]
+    fn from(url: String) -> Self {
+        Self {
+            url,
+            ..Default::default()
+        }
+    }
+}
+
+/// The results of a search starting at some offset
+#[derive(Debug, Default)]
+pub struct Page {
+    /// The number of all results of the keyword
+    pub total: usize,
+    pub images: Vec<Found>,
+}
+
+/// A service searching images by keyword, page by page
+pub trait ImageSource: Debug + Send + Sync {
+    /// The name the downloaded images are credited to
+    fn name(&self) -> &str;
+
+    /// The number of results requested at once
+    fn page_size(&self) -> usize;
+
+    /// The results starting at `start`
+    fn search(
+        &self,
+        client: Arc<HttpClient>,
+        keyword: String,
+        start: usize,
+    ) -> BoxFuture<'static, Result<Page>>;
+
+    /// The request downloading an image found by the search
+    fn download(&self, url: &str) -> Result<Request<()>> {
+        let url = url.parse::<Uri>()?;
+        let host = url
+            .host()
+            .ok_or_else(|| anyhow!("missing host: {}", url))?
+            .to_owned();
+        Ok(Request::builder()
+            .method(Method::GET)
+            .header("Host", host)
+            .uri(url)
+            // HTTP/2 where TLS negotiates it, plain HTTP/1.1 servers are reachable as well
+            .version_negotiation(VersionNegotiation::latest_compatible())
+            .body(())?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Source {
+    Baidu,
+    Pixabay,
+    Json,
+    Manifest,
+}
+
+impl Source {
+    pub const ALL: [Self; 4] = [Self::Baidu, Self::Pixabay, Self::Json, Self::Manifest];
+
+    pub fn build(self, config: &SourceConfig) -> Result<Arc<dyn ImageSource>> {
+        Ok(match self {
+            Self::Baidu => Arc::new(Baidu::new(config.url.clone())),
+            Self::Pixabay => Arc::new(Pixabay::new(config)?),
+            Self::Json => Arc::new(JsonEndpoint::new(config)?),
+            Self::Manifest => Arc::new(Manifest::new(config)?),
+        })
+    }
+
+    /// The provider and the keyword searching images of one of `COLORS`, the providers
+    /// without a color filter search the color name along with the keyword
+    pub fn colored(
+        self,
+        config: &SourceConfig,
+        keyword: &str,
+        color: &str,
+    ) -> Result<(Arc<dyn ImageSource>, String)> {
+        match self {
+            Self::Pixabay => {
+                let config = SourceConfig {
+                    colors: vec![color.into()],
+                    ..config.clone()
+                };
+                Ok((self.build(&config)?, keyword.into()))
+            }
+            _ => Ok((self.build(config)?, format!("{} {}", keyword, color))),
+        }
+    }
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self::Baidu
+    }
+}
+
+impl From<Source> for String {
+    fn from(source: Source) -> Self {
+        Self::from(match source {
+            Source::Baidu => "Baidu",
+            Source::Pixabay => "Pixabay",
+            Source::Json => "JSON",
+            Source::Manifest => "Manifest",
+        })
+    }
+}
+
+pub fn str2source(source: &str) -> std::result::Result<Source, String> {
+    match source {
+        "baidu" => Ok(Source::Baidu),
+        "pixabay" => Ok(Source::Pixabay),
+        "json" => Ok(Source::Json),
+        "manifest" => Ok(Source::Manifest),
+        _ => Err("incorrect source".into()),
+    }
+}
+
+/// The options of the providers, each one reads those it supports
+#[derive(Debug, Clone)]
+pub struct SourceConfig {
+    /// The base url, the public address if there is none. The url template of a JSON endpoint
+    /// or the path of a manifest
+    pub url: Option<String>,
+    pub key: Option<String>,
+    /// Matches any of the colors
+    pub colors: Vec<String>,
+    pub image_type: ImageType,
+    pub per_page: usize,
+    pub resolution: Resolution,
+    /// The JSONPath to the image urls of a JSON endpoint
+    pub json_path: Option<String>,
+    /// The JSONPath to the number of results of a JSON endpoint
+    pub total_path: Option<String>,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            key: None,
+            colors: Vec::new(),
+            image_type: ImageType::default(),
+            per_page: 40,
+            resolution: Resolution::default(),
+            json_path: None,
+            total_path: None,
+        }
+    }
+}
+
+pub fn str2color(color: &str) -> std::result::Result<String, String> {
+    match COLORS.contains(&color) {
+        true => Ok(color.into()),
+        false => Err(format!("incorrect color, one of {}", COLORS.join(", "))),
+    }
+}
+
+pub fn str2image_type(typ: &str) -> std::result::Result<ImageType, String> {
+    match typ {
+        "all" => Ok(ImageType::All),
+        "photo" => Ok(ImageType::Photo),
+        "illustration" => Ok(ImageType::Illustration),
+        "vector" => Ok(ImageType::Vector),
+        _ => Err("incorrect image type".into()),
+    }
+}
+
+pub fn str2resolution(resolution: &str) -> std::result::Result<Resolution, String> {
+    match resolution {
+        "preview" => Ok(Resolution::Preview),
+        "small" => Ok(Resolution::Small),
+        "medium" => Ok(Resolution::Medium),
+        "large" => Ok(Resolution::Large),
+        _ => Err("incorrect resolution".into()),
+    }
+}
+
+/// Joins the base url and the query, the values are encoded already
+fn parse_url(base_url: &str, params: &[(&str, String)]) -> Result<Uri> {
+    Ok(format!(
+        "{}?{}",
+        base_url,
+        params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&")
+    )
+    .parse::<Uri>()?)
+}
@@ -0,0 +1,200 @@
+use {
+    super::{parse_url, Found, ImageSource, Page, SourceConfig},
+    crate::Result,
+    anyhow::anyhow,
+    futures::future::{BoxFuture, FutureExt},
+    isahc::{prelude::*, HttpClient},
+    serde::Deserialize,
+    std::{env, sync::Arc},
+    urlencoding::encode,
+};
+
+// See [Search Images](https://pixabay.com/api/docs/#api_search_images)
+const BASE_URL: &str = "https://pixabay.com/api/";
+/// Read when no key is given
+const KEY_VAR: &str = "PIXABAY_API_KEY";
+const MIN_PER_PAGE: usize = 3;
+const MAX_PER_PAGE: usize = 200;
+// See [Content License](https://pixabay.com/service/license-summary/)
+const LICENSE: &str = "Pixabay Content License";
+
+pub const COLORS: [&str; 14] = [
+    "grayscale",
+    "transparent",
+    "red",
+    "orange",
+    "yellow",
+    "green",
+    "turquoise",
+    "blue",
+    "lilac",
+    "pink",
+    "white",
+    "gray",
+    "black",
+    "brown",
+];
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize)]
+struct Hit {
+    pageURL: Option<String>,
+    previewURL: Option<String>,
+    webformatURL: Option<String>,
+    largeImageURL: Option<String>,
+    /// The name of the contributor
+    user: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize)]
+struct Res {
+    /// The number of results accessible through the API
+    totalHits: usize,
+    hits: Vec<Hit>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ImageType {
+    All,
+    Photo,
+    Illustration,
+    Vector,
+}
+
+impl Default for ImageType {
+    fn default() -> Self {
+        Self::Photo
+    }
+}
+
+impl From<ImageType> for &'static str {
+    fn from(typ: ImageType) -> Self {
+        match typ {
+            ImageType::All => "all",
+            ImageType::Photo => "photo",
+            ImageType::Illustration => "illustration",
+            ImageType::Vector => "vector",
+        }
+    }
+}
+
+/// The size of the downloaded images
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Resolution {
+    /// At most 150 px
+    Preview,
+    /// At most 340 px
+    Small,
+    /// At most 640 px
+    Medium,
+    /// At most 1280 px
+    Large,
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self::Small
+    }
+}
+
+/// The image search of Pixabay, it needs an API key
+#[derive(Debug, Clone)]
+pub struct Pixabay {
+    base_url: String,
+    key: String,
+    colors: String,
+    image_type: ImageType,
+    per_page: usize,
+    resolution: Resolution,
+}
+
+impl Pixabay {
+    pub fn new(config: &SourceConfig) -> Result<Self> {
+        let key = match &config.key {
+            Some(key) => key.clone(),
+            None => env::var(KEY_VAR)
+                .map_err(|_| anyhow!("missing the API key of Pixabay, set {}", KEY_VAR))?,
+        };
+        Ok(Self {
+            base_url: config.url.clone().unwrap_or_else(|| BASE_URL.into()),
+            key,
+            colors: config.colors.join(","),
+            image_type: config.image_type,
+            per_page: config.per_page.clamp(MIN_PER_PAGE, MAX_PER_PAGE),
+            resolution: config.resolution,
+        })
+    }
+
+    fn params(&self, keyword: &str, start: usize) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("key", encode(&self.key).to_string()),
+            ("q", encode(keyword).to_string()),
+            ("image_type", <&str>::from(self.image_type).into()),
+            ("page", (start / self.per_page + 1).to_string()),
+            ("per_page", self.per_page.to_string()),
+        ];
+        if !self.colors.is_empty() {
+            params.push(("colors", encode(&self.colors).to_string()));
+        }
+        params
+    }
+
+    fn found(&self, hit: Hit) -> Option<Found> {
+        let Hit {
+            pageURL,
+            previewURL,
+            webformatURL,
+            largeImageURL,
+            user,
+        } = hit;
+        let url = match self.resolution {
+            Resolution::Preview => previewURL,
+            // the sizes of the web format differ in the suffix only
+            Resolution::Small => webformatURL.map(|url| url.replace("_640.", "_340.")),
+            Resolution::Medium => webformatURL,
+            Resolution::Large => largeImageURL,
+        }?;
+        Some(Found {
+            url,
+            author: user,
+            license: Some(LICENSE.into()),
+            page_url: pageURL,
+        })
+    }
+}
+
+impl ImageSource for Pixabay {
+    #[inline(always)]
+    fn name(&self) -> &str {
+        "Pixabay"
+    }
+
+    #[inline(always)]
+    fn page_size(&self) -> usize {
+        self.per_page
+    }
+
+    fn search(
+        &self,
+        client: Arc<HttpClient>,
+        keyword: String,
+        start: usize,
+    ) -> BoxFuture<'static, Result<Page>> {
+        let url = parse_url(&self.base_url, &self.params(&keyword, start));
+        let this = self.clone();
+        async move {
+            let mut res = client.get_async(url?).await?;
+            if !res.status().is_success() {
+                let text = res.text().await.unwrap_or_default();
+                return Err(anyhow!("Pixabay responded {}: {}", res.status(), text));
+            }
+            let Res { totalHits, hits } = res.json::<Res>().await?;
+            Ok(Page {
+                total: totalHits,
+                images: hits.into_iter().filter_map(|hit| this.found(hit)).collect(),
+            })
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,142 @@
+use {
+    crate::DownloadConfig,
+    image::{io::Reader, ImageFormat, ImageOutputFormat},
+    std::{fmt, io::Cursor},
+    video_mosaic_hash::dhash,
+};
+
+/// The outcome of a download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Download {
+    /// Saved now or by an earlier crawl into the folder
+    Saved,
+    Rejected(Rejection),
+}
+
+/// Why a downloaded file is not kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// The file is smaller or larger than allowed, in bytes
+    Bytes(usize),
+    /// The file is no image that can be decoded, e.g. an error page
+    Undecodable(String),
+    /// The image is smaller or larger than allowed, in pixels
+    Dimensions(u32, u32),
+    /// The image is this many bits of the perceptual hash away from an earlier one
+    Duplicate(u32),
+    /// An earlier crawl of the folder rejected the url, so it is not downloaded again
+    Earlier,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bytes(len) => write!(f, "{} bytes", len),
+            Self::Undecodable(err) => write!(f, "not an image: {}", err),
+            Self::Dimensions(width, height) => write!(f, "{}x{} pixels", width, height),
+            Self::Duplicate(distance) => write!(f, "a near duplicate, {} bits apart", distance),
+            Self::Earlier => write!(f, "rejected by an earlier crawl"),
+        }
+    }
+}
+
+/// A checked download
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Image {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) ext: &'static str,
+    pub(crate) hash: u64,
+}
+
+/// Decodes the file whatever its content type, PNG and JPEG are kept as they are and the other
+/// formats are converted to PNG. The dimensions are read from the header first, so that no
+/// image is decompressed beyond them
+pub(crate) fn normalize(bytes: Vec<u8>, config: &DownloadConfig) -> Result<Image, Rejection> {
+    if bytes.len() < config.min_bytes || bytes.len() > config.max_bytes {
+        return Err(Rejection::Bytes(bytes.len()));
+    }
+    let undecodable = |err: image::ImageError| Rejection::Undecodable(err.to_string());
+    let format = image::guess_format(&bytes).map_err(undecodable)?;
+    let (width, height) = Reader::with_format(Cursor::new(&bytes), format)
+        .into_dimensions()
+        .map_err(undecodable)?;
+    if width.min(height) < config.min_side || width.max(height) > config.max_side {
+        return Err(Rejection::Dimensions(width, height));
+    }
+    let img = image::load_from_memory_with_format(&bytes, format).map_err(undecodable)?;
+
+    let hash = dhash(&img);
+    let (bytes, ext) = match format {
+        ImageFormat::Png => (bytes, "png"),
+        ImageFormat::Jpeg => (bytes, "jpg"),
+        _ => {
+            let mut buf = Vec::new();
+            img.write_to(&mut buf, ImageOutputFormat::Png)
+                .map_err(undecodable)?;
+            (buf, "png")
+        }
+    };
+    Ok(Image { bytes, ext, hash })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{normalize, Rejection},
+        crate::DownloadConfig,
+        image::{DynamicImage, ImageFormat, ImageOutputFormat},
+    };
+
+    fn encode(size: u32, format: ImageOutputFormat) -> Vec<u8> {
+        let mut buf = Vec::new();
+        DynamicImage::new_rgb8(size, size)
+            .write_to(&mut buf, format)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn normalize_images() {
+        let config = DownloadConfig {
+            max_bytes: 4096,
+            min_side: 16,
+            max_side: 64,
+            ..Default::default()
+        };
+
+        let png = encode(32, ImageOutputFormat::Png);
+        let img = normalize(png.clone(), &config).unwrap();
+        assert_eq!((img.bytes, img.ext), (png, "png"));
+        let jpeg = encode(32, ImageOutputFormat::Jpeg(80));
+        let img = normalize(jpeg.clone(), &config).unwrap();
+        assert_eq!((img.bytes, img.ext), (jpeg, "jpg"));
+
+        let bmp = normalize(encode(32, ImageOutputFormat::Bmp), &config).unwrap();
+        assert_eq!(bmp.ext, "png");
+        assert_eq!(image::guess_format(&bmp.bytes).unwrap(), ImageFormat::Png);
+
+        assert!(matches!(
+            normalize(b"<html>Not Found</html>".to_vec(), &config),
+            Err(Rejection::Undecodable(_))
+        ));
+        assert_eq!(
+            normalize(encode(8, ImageOutputFormat::Png), &config),
+            Err(Rejection::Dimensions(8, 8))
+        );
+        assert_eq!(
+            normalize(encode(128, ImageOutputFormat::Png), &config),
+            Err(Rejection::Dimensions(128, 128))
+        );
+        // rejected by the header, before the pixels it claims are decoded
+        let mut bomb = encode(32, ImageOutputFormat::Bmp);
+        bomb[18..26].copy_from_slice(&[0x20, 0x4e, 0, 0, 0x20, 0x4e, 0, 0]);
+        assert_eq!(
+            normalize(bomb, &config),
+            Err(Rejection::Dimensions(20000, 20000))
+        );
+        assert_eq!(
+            normalize(vec![0; 8192], &config),
+            Err(Rejection::Bytes(8192))
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The input cannot be opened or decoded
+    UnsupportedFormat(String),
+    /// The input has no video stream
+    MissingVideoStream,
+    /// The video stream tells no frame rate and the fallback is disabled
+    MissingFrameRate,
+    /// The encoder cannot be found or opened
+    EncoderUnavailable(String),
+    /// The output cannot be encoded or written
+    WriteFailure(String),
+    /// A frame is filled before `pre_fill` provides one
+    MissingFrame,
+    /// The thread pool cannot be built or one of its jobs panicked
+    ThreadFailure(String),
+    /// The library is replaced while the tasks of the previous one still use it
+    LibraryInUse,
+}
+
+impl Error {
+    #[inline(always)]
+    pub(crate) fn unsupported(err: impl fmt::Display) -> Self {
+        Self::UnsupportedFormat(err.to_string())
+    }
+
+    #[inline(always)]
+    pub(crate) fn encoder(err: impl fmt::Display) -> Self {
+        Self::EncoderUnavailable(err.to_string())
+    }
+
+    #[inline(always)]
+    pub(crate) fn write(err: impl fmt::Display) -> Self {
+        Self::WriteFailure(err.to_string())
+    }
+
+    #[inline(always)]
+    pub(crate) fn thread(err: impl fmt::Display) -> Self {
+        Self::ThreadFailure(err.to_string())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(err) => write!(f, "unsupported format: {}", err),
+            Self::MissingVideoStream => write!(f, "missing video stream"),
+            Self::MissingFrameRate => write!(f, "missing frame rate"),
+            Self::EncoderUnavailable(err) => write!(f, "encoder unavailable: {}", err),
+            Self::WriteFailure(err) => write!(f, "write failure: {}", err),
+            Self::MissingFrame => write!(f, "missing frame to fill"),
+            Self::ThreadFailure(err) => write!(f, "thread failure: {}", err),
+            Self::LibraryInUse => write!(f, "library still in use"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// What the output leaves out of the input without failing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The subtitle stream, by its index and codec, that the output cannot hold
+    DroppedSubtitles(usize, String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DroppedSubtitles(index, codec) => write!(
+                f,
+                "dropped the {} subtitles of stream {} the output cannot hold",
+                codec, index
+            ),
+        }
+    }
+}
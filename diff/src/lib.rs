@@ -3,6 +3,7 @@
 
 extern crate ffmpeg_next as ffmpeg;
 
+mod error;
 mod process;
 mod utils;
 
@@ -12,34 +13,45 @@ use {
         media,
         util::frame::video::Video,
     },
+    image::RgbImage,
     std::path::Path,
 };
 
-pub use {ffmpeg::Error, process::*, utils::*};
+pub use {
+    error::{Error, Warning},
+    process::*,
+    utils::*,
+};
 
 pub fn init() {
     ffmpeg::init().unwrap();
     ffmpeg::log::set_level(ffmpeg::log::Level::Quiet);
 }
 
-pub fn first_frame(filename: &Path) -> Result<(u32, u32, Vec<u8>), ffmpeg::Error> {
-    let mut ictx = input(&filename)?;
+pub fn first_frame(filename: &Path) -> Result<(u32, u32, Vec<u8>), Error> {
+    let mut ictx = input(&filename).map_err(Error::unsupported)?;
     let input = ictx
         .streams()
         .best(media::Type::Video)
-        .ok_or(ffmpeg::Error::StreamNotFound)?;
+        .ok_or(Error::MissingVideoStream)?;
 
     let stream_idx = input.index();
-    let mut decoder = input.codec().decoder().video()?;
-    let mut converter = decoder.converter(Pixel::BGRA)?;
+    let mut decoder = input
+        .codec()
+        .decoder()
+        .video()
+        .map_err(Error::unsupported)?;
+    let mut converter = decoder.converter(Pixel::BGRA).map_err(Error::unsupported)?;
 
     for (stream, packet) in ictx.packets() {
         if stream.index() == stream_idx {
-            decoder.send_packet(&packet)?;
+            decoder.send_packet(&packet).map_err(Error::unsupported)?;
             let mut decoded = Video::empty();
             if decoder.receive_frame(&mut decoded).is_ok() {
                 let mut rgb_frame = Video::empty();
-                converter.run(&decoded, &mut rgb_frame)?;
+                converter
+                    .run(&decoded, &mut rgb_frame)
+                    .map_err(Error::unsupported)?;
                 return Ok((
                     decoder.width(),
                     decoder.height(),
@@ -49,7 +61,79 @@ pub fn first_frame(filename: &Path) -> Result<(u32, u32, Vec<u8>), ffmpeg::Error
         }
     }
 
-    Err(ffmpeg::Error::InvalidData)
+    Err(Error::unsupported(ffmpeg::Error::InvalidData))
+}
+
+/// Decodes up to `count` frames spread evenly over the video,
+/// only the first one if the duration is unknown. An image is a single frame
+pub fn sample_frames(filename: &Path, video: bool, count: usize) -> Result<Vec<RgbImage>, Error> {
+    if !video {
+        let img = image::open(filename).map_err(Error::unsupported)?;
+        return Ok(vec![img.into_rgb8()]);
+    }
+
+    let mut ictx = input(&filename).map_err(Error::unsupported)?;
+    let input = ictx
+        .streams()
+        .best(media::Type::Video)
+        .ok_or(Error::MissingVideoStream)?;
+
+    let stream_idx = input.index();
+    let mut decoder = input
+        .codec()
+        .decoder()
+        .video()
+        .map_err(Error::unsupported)?;
+    let mut converter = decoder
+        .converter(Pixel::RGB24)
+        .map_err(Error::unsupported)?;
+
+    let duration = ictx.duration();
+    let count = match duration > 0 {
+        true => count as i64,
+        false => 1,
+    };
+    let mut frames = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        if duration > 0 {
+            let timestamp = duration * (2 * i + 1) / (2 * count);
+            if ictx.seek(timestamp, ..timestamp).is_err() {
+                break;
+            }
+            decoder.flush();
+        }
+
+        let mut decoded = Video::empty();
+        let mut found = false;
+        for (stream, packet) in ictx.packets() {
+            if stream.index() == stream_idx
+                && decoder.send_packet(&packet).is_ok()
+                && decoder.receive_frame(&mut decoded).is_ok()
+            {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            break;
+        }
+
+        let mut rgb_frame = Video::empty();
+        converter
+            .run(&decoded, &mut rgb_frame)
+            .map_err(Error::unsupported)?;
+        let stride = rgb_frame.stride(0);
+        let data = rgb_frame.data(0);
+        frames.push(RgbImage::from_fn(
+            rgb_frame.width(),
+            rgb_frame.height(),
+            |x, y| {
+                let idx = y as usize * stride + x as usize * 3;
+                [data[idx], data[idx + 1], data[idx + 2]].into()
+            },
+        ));
+    }
+    Ok(frames)
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -70,6 +154,10 @@ pub enum ColorSpace {
     RGB,
     HSV,
     CIELAB,
+    CIELCH,
+    OKLAB,
+    YCBCR,
+    XYZ,
 }
 
 impl Default for ColorSpace {
@@ -84,6 +172,10 @@ impl From<ColorSpace> for String {
             ColorSpace::RGB => "RGB",
             ColorSpace::HSV => "HSV",
             ColorSpace::CIELAB => "CIE L*a*b*",
+            ColorSpace::CIELCH => "CIE LCh(ab)",
+            ColorSpace::OKLAB => "Oklab",
+            ColorSpace::YCBCR => "YCbCr",
+            ColorSpace::XYZ => "CIE XYZ",
         })
     }
 }
@@ -91,6 +183,11 @@ impl From<ColorSpace> for String {
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum DistanceAlgorithm {
     Euclidean,
+    WeightedEuclidean,
+    CIE76,
+    CIE94GraphicArts,
+    CIE94Textiles,
+    CMC,
     CIEDE2000,
 }
 
@@ -104,6 +201,11 @@ impl From<DistanceAlgorithm> for String {
     fn from(da: DistanceAlgorithm) -> Self {
         Self::from(match da {
             DistanceAlgorithm::Euclidean => "Euclidean",
+            DistanceAlgorithm::WeightedEuclidean => "Weighted Euclidean",
+            DistanceAlgorithm::CIE76 => "CIE76",
+            DistanceAlgorithm::CIE94GraphicArts => "CIE94 (graphic arts)",
+            DistanceAlgorithm::CIE94Textiles => "CIE94 (textiles)",
+            DistanceAlgorithm::CMC => "CMC l:c",
             DistanceAlgorithm::CIEDE2000 => "CIEDE2000",
         })
     }
@@ -123,6 +225,10 @@ pub fn str2cs(cs: &str) -> Result<ColorSpace, String> {
         "rgb" => Ok(ColorSpace::RGB),
         "hsv" => Ok(ColorSpace::HSV),
         "cielab" => Ok(ColorSpace::CIELAB),
+        "cielch" => Ok(ColorSpace::CIELCH),
+        "oklab" => Ok(ColorSpace::OKLAB),
+        "ycbcr" => Ok(ColorSpace::YCBCR),
+        "xyz" => Ok(ColorSpace::XYZ),
         _ => Err("incorrect color space".into()),
     }
 }
@@ -130,11 +236,36 @@ pub fn str2cs(cs: &str) -> Result<ColorSpace, String> {
 pub fn str2da(da: &str) -> Result<DistanceAlgorithm, String> {
     match da {
         "euclidean" => Ok(DistanceAlgorithm::Euclidean),
+        "weighted_euclidean" => Ok(DistanceAlgorithm::WeightedEuclidean),
+        "cie76" => Ok(DistanceAlgorithm::CIE76),
+        "cie94" => Ok(DistanceAlgorithm::CIE94GraphicArts),
+        "cie94_textiles" => Ok(DistanceAlgorithm::CIE94Textiles),
+        "cmc" => Ok(DistanceAlgorithm::CMC),
         "ciede2000" => Ok(DistanceAlgorithm::CIEDE2000),
         _ => Err("incorrect distance algorithm".into()),
     }
 }
 
+pub fn str2weights(weights: &str) -> Result<[f32; 3], String> {
+    let weights = weights
+        .split(',')
+        .map(|w| w.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| String::from("incorrect weights"))?;
+    match weights[..] {
+        [a, b, c] => Ok([a, b, c]),
+        _ => Err("incorrect weights".into()),
+    }
+}
+
+pub fn str2cmc(cmc: &str) -> Result<(f32, f32), String> {
+    match cmc.split_once(':').map(|(l, c)| (l.parse(), c.parse())) {
+        // the weights divide the differences
+        Some((Ok(l), Ok(c))) if l > 0. && c > 0. => Ok((l, c)),
+        _ => Err("incorrect l:c".into()),
+    }
+}
+
 pub fn str2filter(filter: &str) -> Result<Filter, String> {
     match filter {
         "nearest" => Ok(Filter::Nearest),
@@ -145,3 +276,65 @@ pub fn str2filter(filter: &str) -> Result<Filter, String> {
         _ => Err("incorrect filter".into()),
     }
 }
+
+/// Parses `[[hh:]mm:]ss[.sss]` into seconds
+pub fn str2time(time: &str) -> Result<f64, String> {
+    let parts = time.split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return Err("incorrect time".into());
+    }
+    parts
+        .into_iter()
+        .try_fold(0., |acc, part| match part.trim().parse::<f64>() {
+            Ok(value) if value >= 0. => Ok(acc * 60. + value),
+            _ => Err("incorrect time".into()),
+        })
+}
+
+pub fn str2audio(audio: &str) -> Result<Audio, String> {
+    match audio {
+        "keep" => Ok(Audio::Keep),
+        "drop" => Ok(Audio::Drop),
+        "aac" => Ok(Audio::Aac),
+        "opus" => Ok(Audio::Opus),
+        _ => Err("incorrect audio".into()),
+    }
+}
+
+pub fn str2scaling(scaling: &str) -> Result<Scaling, String> {
+    match scaling {
+        "fast_bilinear" => Ok(Scaling::FastBilinear),
+        "bilinear" => Ok(Scaling::Bilinear),
+        "bicubic" => Ok(Scaling::Bicubic),
+        "lanczos" => Ok(Scaling::Lanczos),
+        "spline" => Ok(Scaling::Spline),
+        _ => Err("incorrect scaling".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Audio;
+
+    #[test]
+    fn audio() {
+        let cases = [
+            ("keep", Audio::Keep),
+            ("drop", Audio::Drop),
+            ("aac", Audio::Aac),
+            ("opus", Audio::Opus),
+        ];
+        for (audio, expected) in cases {
+            assert_eq!(super::str2audio(audio), Ok(expected));
+        }
+        assert!(super::str2audio("mp3").is_err());
+    }
+
+    #[test]
+    fn cmc_weights() {
+        assert_eq!(super::str2cmc("2:1"), Ok((2., 1.)));
+        for cmc in ["0:1", "2:0", "-1:1", "2:NaN", "2", "a:b"] {
+            assert!(super::str2cmc(cmc).is_err(), "{}", cmc);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use {
-    super::{impl_process, Converter, Distance, LibItem, Mask},
-    crate::utils::RawColor,
+    super::{impl_process, Cartesian, Converter, Distance, Frame, LibItem, Mask},
+    crate::utils::{Integral, Palette, RawColor},
     image::{self, imageops::FilterType, Pixel, RgbImage},
 };
 
@@ -9,32 +9,41 @@ pub(super) struct AverageImpl {
     filter: FilterType,
     converter: Converter,
     distance: Distance,
-    lib_color: Box<[RawColor]>,
+    cartesian: Option<Cartesian>,
+    lib_color: Box<[Palette]>,
     lib_image: Box<[RgbImage]>,
-    prev: Option<RgbImage>,
-    next: Option<RgbImage>,
 }
 
 impl_process!(AverageImpl;
     #[inline(always)]
     fn index_step(&self, img: RgbImage) -> LibItem {
-        (self.average(&img, (0, 0, self.size, self.size)), img)
+        let (width, height) = img.dimensions();
+        let raw = self.average(&self.integral(&img), (0, 0, width, height));
+        (Box::new([(raw, 1.)]), img)
     }
 
     #[inline(always)]
-    fn fill_step(&self, mask: Mask) -> (Mask, usize) {
+    fn pre_fill_step(&self, frame: &mut Frame) {
+        frame.integral = Some(self.integral(&frame.img));
+    }
+
+    #[inline(always)]
+    fn fill_step(&self, frame: &Frame, mask: Mask) -> (Mask, usize) {
         let Self {
             distance,
             lib_color,
-            next,
             ..
         } = self;
-        let raw = &self.average(next.as_ref().unwrap(), mask);
+        let raw = &self.average(frame.integral.as_ref().unwrap(), mask);
 
         let (idx, _) = lib_color
             .iter()
             .enumerate()
-            .min_by(|(_, a), (_, b)| distance(a, raw).partial_cmp(&distance(b, raw)).unwrap())
+            .min_by(|(_, a), (_, b)| {
+                distance(&a[0].0, raw)
+                    .partial_cmp(&distance(&b[0].0, raw))
+                    .unwrap()
+            })
             .unwrap();
 
         (mask, idx)
@@ -48,35 +57,41 @@ impl AverageImpl {
         filter: FilterType,
         converter: Converter,
         distance: Distance,
+        cartesian: Option<Cartesian>,
     ) -> Self {
         Self {
             size,
             filter,
             converter,
             distance,
+            cartesian,
             lib_color: Vec::new().into_boxed_slice(),
             lib_image: Vec::new().into_boxed_slice(),
-            prev: None,
-            next: None,
         }
     }
 
-    // #[inline(always)]
-    fn average(&self, img: &RgbImage, (x, y, w, h): Mask) -> RawColor {
-        let Self { converter, .. } = self;
-        let mut ans = [0f32; 3];
-        for j in y..(y + h) {
-            for i in x..(x + w) {
-                let raw = converter(img.get_pixel(i, j).channels());
-                ans[0] += raw[0];
-                ans[1] += raw[1];
-                ans[2] += raw[2];
+    /// Sums the converted pixels, in cartesian coordinates if the color space has a hue
+    fn integral(&self, img: &RgbImage) -> Integral {
+        let Self {
+            converter,
+            cartesian,
+            ..
+        } = self;
+        Integral::new(img.width(), img.height(), false, |x, y| {
+            let raw = converter(img.get_pixel(x, y).channels());
+            match cartesian {
+                Some((to, _)) => to(&raw),
+                None => raw,
             }
+        })
+    }
+
+    #[inline(always)]
+    fn average(&self, integral: &Integral, mask: Mask) -> RawColor {
+        let ans = integral.mean(mask);
+        match self.cartesian {
+            Some((_, from)) => from(&ans),
+            None => ans,
         }
-        let count = (w * h) as f32;
-        ans[0] /= count;
-        ans[1] /= count;
-        ans[2] /= count;
-        ans
     }
 }
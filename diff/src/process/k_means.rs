@@ -1,59 +1,73 @@
 use {
-    super::{impl_process, ColorSpace, Distance, LibItem, Mask},
-    crate::utils::{Color, MyHsv, MyLab, MySrgb, RawColor},
-    image::{self, imageops::FilterType, Pixel as ImagePixel, RgbImage},
-    kmeans_colors::{get_kmeans, get_kmeans_hamerly, Kmeans},
-    palette::{IntoColor, Pixel, Srgb},
+    super::{impl_process, ColorSpace, Converter, Distance, Frame, LibItem, Mask, ProcessConfig},
+    crate::utils::{
+        Cluster, ColorImage, MyHsv, MyLab, MyLch, MyOklab, MySrgb, MyXyz, Palette, RawColor, YCbCr,
+    },
+    image::{self, imageops::FilterType, RgbImage},
+    kmeans_colors::{get_kmeans, get_kmeans_hamerly, Kmeans, Sort},
 };
 
+type KMeans = Box<dyn Fn(&Params, &ColorImage, Mask) -> Palette + Sync + Send>;
+
 pub(super) struct KMeansImpl {
     size: u32,
-    k: usize,
-    converge: f32,
-    max_iter: usize,
+    params: Params,
     filter: FilterType,
+    converter: Converter,
     distance: Distance,
-    k_means: Box<dyn Fn(usize, usize, f32, &RgbImage, Mask) -> RawColor + Sync + Send>,
-    lib_color: Box<[RawColor]>,
+    k_means: KMeans,
+    lib_color: Box<[Palette]>,
     lib_image: Box<[RgbImage]>,
-    prev: Option<RgbImage>,
-    next: Option<RgbImage>,
+}
+
+#[derive(Clone, Copy)]
+struct Params {
+    k: usize,
+    max_iter: usize,
+    converge: f32,
+    seed: u64,
+    runs: u64,
 }
 
 impl_process!(KMeansImpl;
     #[inline(always)]
     fn index_step(&self, img: RgbImage) -> LibItem {
-        let Self { k_means, .. } = self;
-        (
-            k_means(
-                self.k,
-                self.max_iter,
-                self.converge,
-                &img,
-                (0, 0, self.size, self.size),
-            ),
-            img,
-        )
+        let Self {
+            params,
+            converter,
+            k_means,
+            ..
+        } = self;
+        let (width, height) = img.dimensions();
+        let converted = ColorImage::new(&img, converter);
+        (k_means(params, &converted, (0, 0, width, height)), img)
+    }
+
+    #[inline(always)]
+    fn pre_fill_step(&self, frame: &mut Frame) {
+        frame.converted = Some(ColorImage::new(&frame.img, &self.converter));
     }
 
     #[inline(always)]
-    fn fill_step(&self, mask: Mask) -> (Mask, usize) {
+    fn color_distance(&self, raw: &RawColor, palette: &[(RawColor, f32)]) -> f32 {
+        self.palette_distance(&[(*raw, 1.)], palette)
+    }
+
+    #[inline(always)]
+    fn fill_step(&self, frame: &Frame, mask: Mask) -> (Mask, usize) {
         let Self {
-            k,
-            converge,
-            max_iter,
-            distance,
+            params,
             k_means,
             lib_color,
-            next,
             ..
         } = self;
-        let raw = &k_means(*k, *max_iter, *converge, next.as_ref().unwrap(), mask);
+        let palette = &k_means(params, frame.converted.as_ref().unwrap(), mask);
 
         let (idx, _) = lib_color
             .iter()
+            .map(|other| self.palette_distance(palette, other))
             .enumerate()
-            .min_by(|(_, a), (_, b)| distance(a, raw).partial_cmp(&distance(b, raw)).unwrap())
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
             .unwrap();
 
         (mask, idx)
@@ -68,20 +82,45 @@ impl KMeansImpl {
 
     pub(super) fn new(
         size: u32,
-        k: usize,
-        hamerly: bool,
         filter: FilterType,
+        converter: Converter,
         distance: Distance,
-        color_space: ColorSpace,
+        &ProcessConfig {
+            k,
+            hamerly,
+            seed,
+            runs,
+            converge,
+            max_iter,
+            color_space,
+            ..
+        }: &ProcessConfig,
     ) -> Self {
-        let converge = match color_space {
-            ColorSpace::RGB | ColorSpace::HSV => Self::FACTOR_RGB,
-            ColorSpace::CIELAB => Self::FACTOR_LAB,
-        };
+        // the defaults depend on the space in which the clustering is done
+        let converge = converge.unwrap_or(match color_space {
+            ColorSpace::RGB
+            | ColorSpace::HSV
+            | ColorSpace::OKLAB
+            | ColorSpace::YCBCR
+            | ColorSpace::XYZ => Self::FACTOR_RGB,
+            ColorSpace::CIELAB | ColorSpace::CIELCH => Self::FACTOR_LAB,
+        });
+
+        let max_iter = max_iter.unwrap_or(match color_space {
+            ColorSpace::RGB
+            | ColorSpace::HSV
+            | ColorSpace::OKLAB
+            | ColorSpace::YCBCR
+            | ColorSpace::XYZ => Self::MAX_ITER_RGB,
+            ColorSpace::CIELAB | ColorSpace::CIELCH => Self::MAX_ITER_LAB,
+        });
 
-        let max_iter = match color_space {
-            ColorSpace::RGB | ColorSpace::HSV => Self::MAX_ITER_RGB,
-            ColorSpace::CIELAB => Self::MAX_ITER_LAB,
+        let params = Params {
+            k: k.into(),
+            max_iter,
+            converge,
+            seed,
+            runs: runs.max(1).into(),
         };
 
         let k_means = Box::new(match hamerly {
@@ -89,68 +128,89 @@ impl KMeansImpl {
                 ColorSpace::RGB => k_means_hamerly::<MySrgb>,
                 ColorSpace::HSV => k_means_hamerly::<MyHsv>,
                 ColorSpace::CIELAB => k_means_hamerly::<MyLab>,
+                ColorSpace::CIELCH => k_means_hamerly::<MyLch>,
+                ColorSpace::OKLAB => k_means_hamerly::<MyOklab>,
+                ColorSpace::YCBCR => k_means_hamerly::<YCbCr>,
+                ColorSpace::XYZ => k_means_hamerly::<MyXyz>,
             },
             false => match color_space {
                 ColorSpace::RGB => k_means_std::<MySrgb>,
                 ColorSpace::HSV => k_means_std::<MyHsv>,
                 ColorSpace::CIELAB => k_means_std::<MyLab>,
+                ColorSpace::CIELCH => k_means_std::<MyLch>,
+                ColorSpace::OKLAB => k_means_std::<MyOklab>,
+                ColorSpace::YCBCR => k_means_std::<YCbCr>,
+                ColorSpace::XYZ => k_means_std::<MyXyz>,
             },
         });
 
         Self {
             size,
-            k,
-            converge,
-            max_iter,
+            params,
             filter,
+            converter,
             distance,
             k_means,
             lib_color: Vec::new().into_boxed_slice(),
             lib_image: Vec::new().into_boxed_slice(),
-            prev: None,
-            next: None,
         }
     }
+
+    /// Symmetric weighted nearest-neighbour distance: each color is matched with
+    /// the closest color of the other palette, weighted by its own percentage
+    fn palette_distance(&self, a: &[(RawColor, f32)], b: &[(RawColor, f32)]) -> f32 {
+        let Self { distance, .. } = self;
+        let nearest = |from: &[(RawColor, f32)], to: &[(RawColor, f32)]| {
+            from.iter()
+                .map(|(color, weight)| {
+                    let min = to
+                        .iter()
+                        .map(|(other, _)| distance(color, other))
+                        .fold(f32::INFINITY, f32::min);
+                    weight * min
+                })
+                .sum::<f32>()
+        };
+        (nearest(a, b) + nearest(b, a)) / 2.
+    }
 }
 
 macro_rules! k_means {
     ($f:ident, $name:ident) => {
         // #[inline(always)]
-        fn $name<T: Color>(
-            k: usize,
-            max_iter: usize,
-            converge: f32,
-            img: &RgbImage,
+        /// Runs `runs` attempts seeded with `seed..seed + runs` and keeps the best one,
+        /// so the same parameters always give the same palette.
+        /// The clustering is done in the space `C` itself
+        fn $name<C: Cluster>(
+            &Params {
+                k,
+                max_iter,
+                converge,
+                seed,
+                runs,
+            }: &Params,
+            img: &ColorImage,
             (x, y, w, h): Mask,
-        ) -> RawColor {
-            const RUNS: u64 = 3;
-
-            let mut buf: Vec<T> = Vec::with_capacity((w * h) as usize);
+        ) -> Palette {
+            let mut buf: Vec<C::Point> = Vec::with_capacity((w * h) as usize);
             for j in y..(y + h) {
                 for i in x..(x + w) {
-                    let color = Srgb::from_raw(img.get_pixel(i, j).channels())
-                        .into_format::<f32>()
-                        .into_color();
-                    buf.push(color)
+                    buf.push((*C::from_raw(img.get(i, j))).into_point())
                 }
             }
-            let res = (0..RUNS).fold(Kmeans::new(), |res, i| {
-                let run_res = $f(k, max_iter, converge, false, &buf, i);
+            let res = (0..runs).fold(Kmeans::new(), |res, i| {
+                let run_res = $f(k, max_iter, converge, false, &buf, seed.wrapping_add(i));
                 if run_res.score < res.score {
                     run_res
                 } else {
                     res
                 }
             });
-            let mut res = T::sort_indexed_colors(&res.centroids, &res.indices);
+            let mut res = C::Point::sort_indexed_colors(&res.centroids, &res.indices);
             res.sort_unstable_by(|a, b| (b.percentage).partial_cmp(&a.percentage).unwrap());
-            res.into_iter().fold([0f32; 3], |mut ans, i| {
-                let raw: [f32; 3] = i.centroid.into_raw();
-                ans[0] += raw[0] * i.percentage;
-                ans[1] += raw[1] * i.percentage;
-                ans[2] += raw[2] * i.percentage;
-                ans
-            })
+            res.into_iter()
+                .map(|i| (C::from_point(i.centroid).into_raw(), i.percentage))
+                .collect()
         }
     };
 }
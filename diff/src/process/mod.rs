@@ -4,51 +4,109 @@ mod pixel;
 
 use {
     crate::{
-        ciede2000, converter, CalculationUnit, ColorSpace, DistanceAlgorithm, F32Wrapper,
-        FrameIter, ImageDump, MyHsv, MySrgb, RawColor, Transcode, Variance,
+        cie76, cie94, ciede2000, cmc, cone_to_hsv, converter, gap_scores, hsv_to_cone, keep_unique,
+        lab_to_lch, lch_to_lab, CalculationUnit, ColorImage, ColorSpace, DistanceAlgorithm, Error,
+        F32Wrapper, FrameIter, FrameWriter, Histogram, ImageDump, Integral, MyHsv, MyLab, MyLch,
+        MyOklab, MySrgb, MyXyz, Palette, QualityFilter, RawColor, Stamp, Transcode, Warning, YCbCr,
+        COLOR_BUCKETS,
     },
-    async_std::task::{spawn_blocking, JoinHandle},
     average::AverageImpl,
-    futures::stream::{futures_unordered, FuturesUnordered},
+    ffmpeg::software::scaling::Flags,
+    futures::{
+        channel::oneshot,
+        future::BoxFuture,
+        stream::{futures_unordered, FuturesUnordered},
+    },
     image::{
         imageops::{crop, resize, FilterType},
         GenericImageView, ImageBuffer, Pixel, RgbImage,
     },
     k_means::KMeansImpl,
-    palette::{Lab, Pixel as PalettePixel},
     pixel::PixelImpl,
-    std::{borrow::Cow, collections::BTreeMap, fmt, path::PathBuf, sync::Arc},
+    rayon::{ThreadPool, ThreadPoolBuilder},
+    std::{
+        borrow::Cow,
+        cmp::Reverse,
+        collections::{BTreeMap, VecDeque},
+        fmt,
+        panic::{catch_unwind, AssertUnwindSafe},
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc::{channel, sync_channel, Receiver, SyncSender},
+            Arc, Mutex,
+        },
+        thread,
+    },
+    video_mosaic_hash::dhash,
 };
 
 pub type Mask = (u32, u32, u32, u32);
-type Task<T> = JoinHandle<T>;
+type Task<T> = BoxFuture<'static, T>;
 type Tasks<T> = FuturesUnordered<Task<T>>;
 pub type TasksIter<T> = futures_unordered::IntoIter<Task<T>>;
-pub type LibItem = (RawColor, RgbImage);
+pub type LibItem = (Palette, RgbImage);
+/// A chunk of the library by its position among the chunks
+pub type Indexed = (usize, Vec<Option<LibItem>>);
 
 type Converter = Box<dyn Fn(&[u8]) -> RawColor + Sync + Send>;
 type Distance = Box<dyn Fn(&RawColor, &RawColor) -> f32 + Sync + Send>;
+/// Maps the colors with a hue channel to cartesian coordinates and back,
+/// so that they can be averaged channel by channel
+type Cartesian = (fn(&RawColor) -> RawColor, fn(&RawColor) -> RawColor);
+/// A frame and the tasks filling its masks
+type Filling = (Arc<Frame>, Tasks<Result<Vec<(Mask, usize)>, Error>>);
+type Decoded = Result<(RgbImage, Stamp), Error>;
+/// The sender of the filled frames and the thread writing them
+type Encoder = (
+    SyncSender<(RgbImage, Stamp)>,
+    thread::JoinHandle<Result<(), Error>>,
+);
+/// The frames read ahead, the writer and the frames it is done with
+type Pipeline = (Receiver<Decoded>, Encoder, Receiver<RgbImage>);
+
+/// The share of the distance of black and white within which a color counts as covered
+const GAP_TOLERANCE: f32 = 0.05;
+
+/// A target frame, together with its conversions shared by all of its masks
+struct Frame {
+    img: RgbImage,
+    stamp: Stamp,
+    integral: Option<Integral>,
+    converted: Option<ColorImage>,
+}
 
 trait Process {
     fn size(&self) -> u32;
 
-    fn prev(&self) -> &Option<RgbImage>;
-
-    fn next(&self) -> &Option<RgbImage>;
+    fn set_lib(&mut self, lib_color: Vec<Palette>, lib_image: Vec<RgbImage>);
 
-    fn prev_mut(&mut self) -> &mut Option<RgbImage>;
+    fn get_image(&self, idx: usize) -> &RgbImage;
 
-    fn next_mut(&mut self) -> &mut Option<RgbImage>;
+    fn filter(&self) -> FilterType;
 
-    fn set_lib(&mut self, lib_color: Vec<RawColor>, lib_image: Vec<RgbImage>);
+    /// Converts a pixel to the working color space
+    fn convert(&self, rgb: &[u8]) -> RawColor;
 
-    fn get_image(&self, idx: usize) -> &RgbImage;
+    fn distance(&self, a: &RawColor, b: &RawColor) -> f32;
 
-    fn filter(&self) -> FilterType;
+    /// The distance of a color to a palette, as the matcher compares them
+    fn color_distance(&self, raw: &RawColor, palette: &[(RawColor, f32)]) -> f32 {
+        palette
+            .iter()
+            .map(|(color, weight)| weight * self.distance(raw, color))
+            .sum()
+    }
 
     fn index_step(&self, img: RgbImage) -> LibItem;
 
-    fn fill_step(&self, mask: Mask) -> (Mask, usize);
+    /// Called once the library is set
+    fn post_index_step(&mut self) {}
+
+    /// Called once per frame, before any of its masks is filled
+    fn pre_fill_step(&self, _frame: &mut Frame) {}
+
+    fn fill_step(&self, frame: &Frame, mask: Mask) -> (Mask, usize);
 }
 
 macro_rules! impl_process {
@@ -60,27 +118,7 @@ macro_rules! impl_process {
             }
 
             #[inline(always)]
-            fn prev(&self) -> &Option<RgbImage> {
-                &self.prev
-            }
-
-            #[inline(always)]
-            fn next(&self) -> &Option<RgbImage> {
-                &self.next
-            }
-
-            #[inline(always)]
-            fn prev_mut(&mut self) -> &mut Option<RgbImage> {
-                &mut self.prev
-            }
-
-            #[inline(always)]
-            fn next_mut(&mut self) -> &mut Option<RgbImage> {
-                &mut self.next
-            }
-
-            #[inline(always)]
-            fn set_lib(&mut self, lib_color: Vec<RawColor>, lib_image: Vec<RgbImage>) {
+            fn set_lib(&mut self, lib_color: Vec<Palette>, lib_image: Vec<RgbImage>) {
                 self.lib_color = lib_color.into_boxed_slice();
                 self.lib_image = lib_image.into_boxed_slice();
             }
@@ -95,113 +133,220 @@ macro_rules! impl_process {
                 self.filter
             }
 
+            #[inline(always)]
+            fn convert(&self, rgb: &[u8]) -> $crate::RawColor {
+                (self.converter)(rgb)
+            }
+
+            #[inline(always)]
+            fn distance(&self, a: &$crate::RawColor, b: &$crate::RawColor) -> f32 {
+                (self.distance)(a, b)
+            }
+
             $( $fn )*
         }
     };
 }
 pub(super) use impl_process;
 
+/// Instantiates a function that is generic over the color type of the color space
+macro_rules! color_fn {
+    ($color_space:expr, $fn:ident $(, $arg:tt)*) => {
+        match $color_space {
+            ColorSpace::RGB => $fn::<MySrgb $(, $arg)*>,
+            ColorSpace::HSV => $fn::<MyHsv $(, $arg)*>,
+            ColorSpace::CIELAB => $fn::<MyLab $(, $arg)*>,
+            ColorSpace::CIELCH => $fn::<MyLch $(, $arg)*>,
+            ColorSpace::OKLAB => $fn::<MyOklab $(, $arg)*>,
+            ColorSpace::YCBCR => $fn::<YCbCr $(, $arg)*>,
+            ColorSpace::XYZ => $fn::<MyXyz $(, $arg)*>,
+        }
+    };
+}
+
 // TODO: Quad cannot work with pre-calc color
 
+/// Frames flow through a bounded pipeline: a decoder thread reads up to `lookahead` frames
+/// ahead, the masks of several frames are matched at the same time,
+/// and an encoder thread writes the filled frames in their original order
 pub struct ProcessWrapper {
-    iter: Box<dyn FrameIter + Sync + Send + 'static>,
     inner: Arc<dyn Process + Sync + Send + 'static>,
+    pool: ThreadPool,
+    decoded: Receiver<Decoded>,
+    encoder: Option<Encoder>,
+    /// The written frames, to be filled again
+    recycled: Receiver<RgbImage>,
+    queue: VecDeque<Filling>,
+    /// The histogram of the previous frame, to detect the scene cuts
+    histogram: Option<Histogram>,
+    current: Option<Filling>,
     buf: RgbImage,
-    composite: Option<RgbImage>,
     frames: i64,
     width: u32,
     height: u32,
     quad_iter: Option<usize>,
     overlay: Option<u8>,
+    lookahead: usize,
     masks: Box<[Mask]>,
+    /// The mean color of each mask when it was last filled, in the working color space
+    references: Box<[Option<RawColor>]>,
+    /// The colors with a hue are averaged in cartesian coordinates
+    cartesian: Option<Cartesian>,
+    scene_cut: f32,
+    change_threshold: f32,
+    /// The distance within which the library covers a color of the target
+    gap_tolerance: f32,
+    dedup: Option<u32>,
+    /// The perceptual hashes of the library images indexed so far
+    hashes: Arc<Mutex<Vec<u64>>>,
+    /// The number of near duplicates left out of the index so far
+    removed: Arc<AtomicUsize>,
+    quality: QualityFilter,
+    warnings: Vec<Warning>,
 }
 
 impl ProcessWrapper {
     #[inline(always)]
     pub fn new(
-        ProcessConfig {
+        config: ProcessConfig,
+        input: String,
+        output: String,
+        video: bool,
+    ) -> Result<Self, Error> {
+        let ProcessConfig {
             size,
-            k,
-            hamerly,
             calc_unit,
             color_space,
             dist_algo,
+            weights,
+            cmc: (cmc_l, cmc_c),
             filter,
             quad_iter,
             overlay,
-        }: ProcessConfig,
-        input: String,
-        output: String,
-        video: bool,
-    ) -> Self {
+            lookahead,
+            threads,
+            scene_cut,
+            change_threshold,
+            dedup,
+            quality,
+            ..
+        } = config;
+        let lookahead = lookahead.max(1) as usize;
         let size = size as u32;
         let filter = filter.into();
 
-        let distance = Box::new(match dist_algo {
-            DistanceAlgorithm::Euclidean => match color_space {
+        let distance: Distance = match dist_algo {
+            DistanceAlgorithm::Euclidean => Box::new(match color_space {
                 ColorSpace::HSV => |a: &RawColor, b: &RawColor| {
-                    let a = MyHsv::from_raw(a);
-                    let b = MyHsv::from_raw(b);
-                    (a.hue.to_positive_degrees() - b.hue.to_positive_degrees()).powi(2)
-                        + ((a.saturation - b.saturation) * 360.).powi(2)
-                        + ((a.value - b.value) * 360.).powi(2)
+                    let a = hsv_to_cone(a);
+                    let b = hsv_to_cone(b);
+                    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+                },
+                ColorSpace::CIELCH => |a: &RawColor, b: &RawColor| {
+                    let a = lch_to_lab(a);
+                    let b = lch_to_lab(b);
+                    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
                 },
                 _ => |a: &RawColor, b: &RawColor| {
                     (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
                 },
-            },
-            DistanceAlgorithm::CIEDE2000 => match color_space {
-                ColorSpace::RGB => ciede2000::<MySrgb>,
-                ColorSpace::HSV => ciede2000::<MyHsv>,
-                ColorSpace::CIELAB => ciede2000::<Lab>,
-            },
-        });
+            }),
+            DistanceAlgorithm::WeightedEuclidean => {
+                // the spaces with a hue are weighted by their cartesian coordinates
+                let coords: fn(&RawColor) -> RawColor = match color_space {
+                    ColorSpace::HSV => hsv_to_cone,
+                    ColorSpace::CIELCH => lch_to_lab,
+                    _ => |raw| *raw,
+                };
+                Box::new(move |a: &RawColor, b: &RawColor| {
+                    let a = coords(a);
+                    let b = coords(b);
+                    (0..3).map(|i| weights[i] * (a[i] - b[i]).powi(2)).sum()
+                })
+            }
+            DistanceAlgorithm::CIE76 => Box::new(color_fn!(color_space, cie76)),
+            DistanceAlgorithm::CIE94GraphicArts => Box::new(color_fn!(color_space, cie94, false)),
+            DistanceAlgorithm::CIE94Textiles => Box::new(color_fn!(color_space, cie94, true)),
+            DistanceAlgorithm::CMC => {
+                let f = color_fn!(color_space, cmc);
+                Box::new(move |a: &RawColor, b: &RawColor| f(a, b, cmc_l, cmc_c))
+            }
+            DistanceAlgorithm::CIEDE2000 => Box::new(color_fn!(color_space, ciede2000)),
+        };
 
-        let converter = Box::new(match color_space {
-            ColorSpace::RGB => converter::<MySrgb>,
-            ColorSpace::HSV => converter::<MyHsv>,
-            ColorSpace::CIELAB => converter::<Lab>,
-        });
+        let converter = Box::new(color_fn!(color_space, converter));
+
+        let cartesian: Option<Cartesian> = match color_space {
+            ColorSpace::HSV => Some((hsv_to_cone, cone_to_hsv)),
+            ColorSpace::CIELCH => Some((lch_to_lab, lab_to_lch)),
+            _ => None,
+        };
 
         let inner: Arc<dyn Process + Sync + Send + 'static> = match calc_unit {
-            CalculationUnit::Average => {
-                Arc::new(AverageImpl::new(size, filter, converter, distance))
-            }
-            CalculationUnit::Pixel => Arc::new(PixelImpl::new(size, filter, converter, distance)),
-            CalculationUnit::KMeans => Arc::new(KMeansImpl::new(
-                size,
-                k.into(),
-                hamerly,
-                filter,
-                distance,
-                color_space,
+            CalculationUnit::Average => Arc::new(AverageImpl::new(
+                size, filter, converter, distance, cartesian,
             )),
+            CalculationUnit::Pixel => Arc::new(PixelImpl::new(size, filter, converter, distance)),
+            CalculationUnit::KMeans => {
+                Arc::new(KMeansImpl::new(size, filter, converter, distance, &config))
+            }
         };
 
-        let (iter, frames, width, height) = if video {
-            let (iter, frames, width, height) = Transcode::new(input, output);
-            let iter: Box<dyn FrameIter + Sync + Send + 'static> = Box::new(iter);
-            (iter, frames, width, height)
-        } else {
-            let (iter, frames, width, height) = ImageDump::new(input, output);
-            let iter: Box<dyn FrameIter + Sync + Send + 'static> = Box::new(iter);
-            (iter, frames, width, height)
+        // the threads of the pool steal chunks of masks from each other
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads.unwrap_or_default())
+            .build()
+            .map_err(Error::thread)?;
+
+        let ((decoded, encoder, recycled), warnings, frames, width, height) = match video {
+            true => pipeline::<Transcode>(input, output, &config, lookahead)?,
+            false => pipeline::<ImageDump>(input, output, &config, lookahead)?,
         };
 
-        let composite = overlay.map(|_| ImageBuffer::new(width, height));
+        // the threshold is relative to the largest difference of the color space
+        let black_to_white = inner.distance(&inner.convert(&[0; 3]), &inner.convert(&[u8::MAX; 3]));
+        let squared = matches!(
+            dist_algo,
+            DistanceAlgorithm::Euclidean | DistanceAlgorithm::WeightedEuclidean
+        );
 
-        Self {
-            iter,
+        let mut masks = Vec::with_capacity((((width / size) + 1) * ((height / size) + 1)) as usize);
+        for y in (0..height).step_by(size as usize) {
+            for x in (0..width).step_by(size as usize) {
+                let w = size.min(width - x);
+                let h = size.min(height - y);
+                masks.push((x, y, w, h));
+            }
+        }
+
+        Ok(Self {
             inner,
+            pool,
+            decoded,
+            encoder: Some(encoder),
+            recycled,
+            queue: VecDeque::with_capacity(lookahead),
+            histogram: None,
+            current: None,
             buf: ImageBuffer::new(width, height),
-            composite,
             frames,
             width,
             height,
             quad_iter,
             overlay,
-            masks: Vec::new().into_boxed_slice(),
-        }
+            lookahead,
+            references: vec![None; masks.len()].into_boxed_slice(),
+            cartesian,
+            masks: masks.into_boxed_slice(),
+            scene_cut,
+            change_threshold: relative_distance(change_threshold, black_to_white, squared),
+            gap_tolerance: relative_distance(GAP_TOLERANCE, black_to_white, squared),
+            dedup,
+            hashes: Arc::new(Mutex::new(Vec::new())),
+            removed: Arc::new(AtomicUsize::new(0)),
+            quality,
+            warnings,
+        })
     }
 
     #[inline(always)]
@@ -219,8 +364,41 @@ impl ProcessWrapper {
         self.height
     }
 
+    /// What the output leaves out of the input, e.g. the subtitles it cannot hold
+    #[inline(always)]
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Runs `f` on the thread pool, a panic of `f` is an error
+    fn spawn<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Task<Result<T, Error>> {
+        let (sender, receiver) = oneshot::channel();
+        self.pool.spawn(move || {
+            let _ = sender.send(catch_unwind(AssertUnwindSafe(f)));
+        });
+        Box::pin(async move {
+            match receiver.await {
+                Ok(Ok(ans)) => Ok(ans),
+                _ => Err(Error::thread("a job panicked")),
+            }
+        })
+    }
+
+    /// Several chunks per thread, so that the idle threads can take over the remaining ones
     #[inline(always)]
-    pub fn index(&self, libraries: Vec<PathBuf>) -> Tasks<Option<LibItem>> {
+    fn chunk_size(&self, len: usize) -> usize {
+        const CHUNKS_PER_THREAD: usize = 4;
+        (len / (self.pool.current_num_threads() * CHUNKS_PER_THREAD)).max(1)
+    }
+
+    /// The unreadable images, those failing the quality filter and the near duplicates are none.
+    /// The chunks finish in any order, sorting them by their position keeps the order of the
+    /// library. Of two near duplicates in different chunks the one indexed first is kept
+    #[inline(always)]
+    pub fn index(&self, libraries: Vec<PathBuf>) -> Tasks<Result<Indexed, Error>> {
         let (nwidth, nheight) = match self.quad_iter {
             Some(iterations) => {
                 // 1 + 4 + 16 + ...
@@ -231,52 +409,157 @@ impl ProcessWrapper {
             None => (self.inner.size(), self.inner.size()),
         };
         libraries
-            .into_iter()
-            .map(|lib| {
+            .chunks(self.chunk_size(libraries.len()))
+            .enumerate()
+            .map(|(idx, chunk)| {
                 let inner = self.inner.clone();
-                spawn_blocking(move || {
-                    if let Ok(img) = image::open(lib) {
-                        let img = img
-                            .resize_to_fill(nwidth, nheight, inner.filter())
-                            .into_rgb8();
-                        return Some(inner.index_step(img));
-                    }
-                    None
+                let quality = self.quality;
+                let dedup = self
+                    .dedup
+                    .map(|threshold| (threshold, self.hashes.clone(), self.removed.clone()));
+                let chunk = chunk.to_vec();
+                self.spawn(move || {
+                    let items = chunk
+                        .into_iter()
+                        .map(|lib| {
+                            let img = image::open(lib).ok()?;
+                            if !quality.passes(&img) {
+                                return None;
+                            }
+                            // before the near duplicate is resized and converted
+                            if let Some((threshold, hashes, removed)) = &dedup {
+                                let hash = dhash(&img);
+                                if !keep_unique(&mut hashes.lock().unwrap(), hash, *threshold) {
+                                    removed.fetch_add(1, Ordering::Relaxed);
+                                    return None;
+                                }
+                            }
+                            let img = img
+                                .resize_to_fill(nwidth, nheight, inner.filter())
+                                .into_rgb8();
+                            Some(inner.index_step(img))
+                        })
+                        .collect();
+                    (idx, items)
                 })
             })
             .collect::<FuturesUnordered<_>>()
     }
 
-    #[inline(always)]
-    pub fn post_index(&mut self, lib_color: Vec<RawColor>, lib_image: Vec<RgbImage>) {
-        Arc::get_mut(&mut self.inner)
-            .unwrap()
-            .set_lib(lib_color, lib_image);
+    /// Sets the library, returns how many near duplicates `index` has left out of it.
+    /// The tasks of `index` must be done
+    pub fn post_index(
+        &mut self,
+        lib_color: Vec<Palette>,
+        lib_image: Vec<RgbImage>,
+    ) -> Result<usize, Error> {
+        let inner = Arc::get_mut(&mut self.inner).ok_or(Error::LibraryInUse)?;
+        inner.set_lib(lib_color, lib_image);
+        inner.post_index_step();
+        Ok(self.removed.swap(0, Ordering::Relaxed))
+    }
+
+    /// Ranks the named colors of the frames by how poorly the library covers them, the worst
+    /// first. The colors within a small distance of any palette of the library, as the matcher
+    /// compares them, count as covered. The pixel matcher indexes no palettes, so it finds no gap
+    pub fn color_gaps(
+        &self,
+        frames: &[RgbImage],
+        lib_color: &[Palette],
+    ) -> Vec<(&'static str, f32)> {
+        let inner = &self.inner;
+        let colors = frames
+            .iter()
+            .flat_map(|frame| {
+                let frame = match frame.dimensions() == (self.width, self.height) {
+                    true => Cow::Borrowed(frame),
+                    false => {
+                        Cow::Owned(resize(frame, self.width, self.height, FilterType::Triangle))
+                    }
+                };
+                self.masks
+                    .iter()
+                    .map(|&mask| inner.convert(&mean(&frame, mask)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let buckets = COLOR_BUCKETS
+            .iter()
+            .map(|(_, color)| inner.convert(color))
+            .collect::<Vec<_>>();
+
+        let scores = gap_scores(
+            &colors,
+            lib_color,
+            &buckets,
+            |a, b| inner.distance(a, b),
+            |color, palette| inner.color_distance(color, palette),
+            self.gap_tolerance,
+        );
+        let mut gaps = COLOR_BUCKETS
+            .iter()
+            .zip(scores)
+            .filter(|(_, score)| *score > 0.)
+            .map(|((name, _), score)| (*name, score))
+            .collect::<Vec<_>>();
+        gaps.sort_by_key(|(_, score)| Reverse(F32Wrapper(*score)));
+        gaps
     }
 
+    /// Tops up the pipeline and moves to the next frame, whose masks may be already filled
     #[inline(always)]
-    pub fn pre_fill(&mut self) -> bool {
-        {
-            let inner = Arc::get_mut(&mut self.inner).unwrap();
-            if self.quad_iter.is_none() {
-                *inner.prev_mut() = inner.next_mut().take();
-            }
-            let next = inner.next_mut();
-            *next = self.iter.next();
-            if next.is_none() {
-                self.flush();
-                return false;
-            }
+    pub fn pre_fill(&mut self) -> Result<bool, Error> {
+        while self.queue.len() < self.lookahead {
+            let (img, stamp) = match self.decoded.recv() {
+                Ok(decoded) => decoded?,
+                Err(_) => break,
+            };
+            let mut frame = Frame {
+                img,
+                stamp,
+                integral: None,
+                converted: None,
+            };
+            self.inner.pre_fill_step(&mut frame);
+            let frame = Arc::new(frame);
+
+            let masks = self.masks(&frame);
+            let tasks = masks
+                .chunks(self.chunk_size(masks.len()))
+                .map(|chunk| {
+                    let inner = self.inner.clone();
+                    let frame = frame.clone();
+                    let chunk = chunk.to_vec();
+                    self.spawn(move || {
+                        chunk
+                            .into_iter()
+                            .map(|mask| inner.fill_step(&frame, mask))
+                            .collect()
+                    })
+                })
+                .collect::<FuturesUnordered<_>>();
+
+            self.queue.push_back((frame, tasks));
         }
 
-        let size = self.inner.size();
-        let mut masks =
-            Vec::with_capacity((((self.width / size) + 1) * ((self.height / size) + 1)) as usize);
+        self.current = self.queue.pop_front();
+        if self.current.is_none() {
+            self.flush()?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
 
+    /// The masks of the frame that differ from the previous one
+    fn masks(&mut self, frame: &Frame) -> Vec<Mask> {
+        let next = &frame.img;
         if let Some(iterations) = self.quad_iter {
             const MIN_LENGTH: u32 = 16;
+            let mut masks = Vec::new();
             let min_area = (self.width / 128).max(MIN_LENGTH) * (self.height / 128).max(MIN_LENGTH);
-            let next = self.inner.next().as_ref().unwrap();
+            let integral = Integral::new(self.width, self.height, true, |x, y| {
+                next.get_pixel(x, y).0.map(f32::from)
+            });
             let mut heap: BTreeMap<F32Wrapper, Mask> = BTreeMap::new();
             heap.insert(F32Wrapper(0.), (0, 0, self.width, self.height));
             'outer: for _ in 0..iterations {
@@ -302,79 +585,85 @@ impl ProcessWrapper {
                     (xm, ym, w - w2, h - h2),
                 ];
 
-                for (x, y, w, h) in quad {
-                    let mut rgb = [Variance::new(); 3];
-                    for j in y..(y + h) {
-                        for i in x..(x + w) {
-                            let raw = next.get_pixel(i, j).0;
-                            for (idx, raw) in raw.into_iter().enumerate() {
-                                rgb[idx].next(raw as i64);
-                            }
-                        }
-                    }
-
+                for mask in quad {
                     const FACTOR: [f32; 3] = [0.299, 0.587, 0.114];
-                    let error = rgb
+                    let error = integral
+                        .variance(mask)
                         .into_iter()
-                        .enumerate()
-                        .map(|(idx, part)| part.variance() * FACTOR[idx])
+                        .zip(FACTOR)
+                        .map(|(variance, factor)| variance * factor)
                         .sum::<f32>();
-                    heap.insert(F32Wrapper(error), (x, y, w, h));
+                    heap.insert(F32Wrapper(error), mask);
                 }
             }
 
-            masks.extend_from_slice(&heap.into_values().collect::<Vec<_>>());
-        } else {
-            if self.inner.prev().is_some() {
-                return true;
+            masks.extend(heap.into_values());
+            return masks;
+        }
+
+        // after a scene cut every mask is filled again
+        let histogram = Histogram::new(next);
+        let scene_cut = match &self.histogram {
+            Some(prev) => prev.difference(&histogram) >= self.scene_cut,
+            None => true,
+        };
+        self.histogram = Some(histogram);
+
+        // the average matcher has summed the frame already
+        let summed;
+        let integral = match &frame.integral {
+            Some(integral) => integral,
+            None => {
+                summed = self.integral(frame);
+                &summed
             }
+        };
 
-            for y in (0..self.height).step_by(size as usize) {
-                for x in (0..self.width).step_by(size as usize) {
-                    let w = size.min(self.width - x);
-                    let h = size.min(self.height - y);
-                    masks.push((x, y, w, h));
+        let mut masks = Vec::new();
+        for (&mask, reference) in self.masks.iter().zip(self.references.iter_mut()) {
+            let mean = integral.mean(mask);
+            let mean = match self.cartesian {
+                Some((_, from)) => from(&mean),
+                None => mean,
+            };
+            let changed = match reference {
+                Some(reference) if !scene_cut => {
+                    self.inner.distance(reference, &mean) > self.change_threshold
                 }
+                _ => true,
+            };
+            if changed {
+                *reference = Some(mean);
+                masks.push(mask);
             }
-        };
+        }
+        masks
+    }
 
-        self.masks = masks.into_boxed_slice();
-        true
+    /// Sums the pixels of the frame in the working color space, as the average matcher does,
+    /// from the pixels the matcher has converted if any
+    fn integral(&self, frame: &Frame) -> Integral {
+        let Self {
+            inner, cartesian, ..
+        } = self;
+        Integral::new(self.width, self.height, false, |x, y| {
+            let raw = match &frame.converted {
+                Some(converted) => *converted.get(x, y),
+                None => inner.convert(frame.img.get_pixel(x, y).channels()),
+            };
+            match cartesian {
+                Some((to, _)) => to(&raw),
+                None => raw,
+            }
+        })
     }
 
     #[inline(always)]
-    pub fn fill(&mut self) -> Tasks<(Mask, usize)> {
-        let masks = self.masks.iter();
-        let masks: Box<dyn Iterator<Item = &Mask>> =
-            match self.quad_iter.is_none() && self.inner.prev().is_some() {
-                true => {
-                    let prev = self.inner.prev().as_ref().unwrap();
-                    let next = self.inner.next().as_ref().unwrap();
-                    Box::new(masks.filter(|mask| {
-                        let mut total: usize = 0;
-                        let mut count: usize = 0;
-                        const STEP: usize = 5;
-                        for j in (mask.1..(mask.1 + mask.3)).step_by(STEP) {
-                            for i in (mask.0..(mask.0 + mask.2)).step_by(STEP) {
-                                let prev_pixel = prev.get_pixel(i, j);
-                                let next_pixel = next.get_pixel(i, j);
-                                total += 1;
-                                if prev_pixel != next_pixel {
-                                    count += 1;
-                                }
-                            }
-                        }
-                        count * 2 >= total
-                    }))
-                }
-                false => Box::new(masks),
-            };
-        masks
-            .map(|&mask| {
-                let inner = self.inner.clone();
-                spawn_blocking(move || inner.fill_step(mask))
-            })
-            .collect::<FuturesUnordered<_>>()
+    pub fn fill(&mut self) -> Tasks<Result<Vec<(Mask, usize)>, Error>> {
+        match self.current.as_mut() {
+            Some((_, tasks)) => std::mem::take(tasks),
+            None => FuturesUnordered::new(),
+        }
     }
 
     #[inline(always)]
@@ -442,39 +731,125 @@ impl ProcessWrapper {
 
     /// See [`overlay`](#image::imageops::overlay)
     #[inline(always)]
-    pub fn post_fill(&mut self) {
+    pub fn post_fill(&mut self) -> Result<(), Error> {
+        let (frame, _) = self.current.as_ref().ok_or(Error::MissingFrame)?;
+        let (width, height) = (self.width, self.height);
+        let mut img = self
+            .recycled
+            .try_recv()
+            .unwrap_or_else(|_| ImageBuffer::new(width, height));
         if let Some(bottom_alpha) = self.overlay {
             let top_alpha = u8::MAX - bottom_alpha;
-            let img = self.inner.next().as_ref().unwrap();
-            let composite = self.composite.as_mut().unwrap();
-            for j in 0..self.height {
-                for i in 0..self.width {
+            for j in 0..height {
+                for i in 0..width {
                     let mut top = self.buf.get_pixel(i, j).to_rgba();
-                    let mut bottom = img.get_pixel(i, j).to_rgba();
+                    let mut bottom = frame.img.get_pixel(i, j).to_rgba();
                     top.0[3] = top_alpha;
                     bottom.0[3] = bottom_alpha;
                     bottom.blend(&top);
-                    composite.put_pixel(i, j, bottom.to_rgb());
+                    img.put_pixel(i, j, bottom.to_rgb());
                 }
             }
-            self.iter.post_next(composite)
         } else {
-            self.iter.post_next(&self.buf)
-        };
+            // the masks that did not change are kept in the buffer for the next frame
+            img.copy_from_slice(&self.buf);
+        }
+        let stamp = frame.stamp;
+        if let Some((sender, _)) = &self.encoder {
+            // the encoder thread only hangs up after a failure, which the flush reports
+            if sender.send((img, stamp)).is_err() {
+                return self.flush();
+            }
+        }
+        Ok(())
     }
 
+    /// Waits for the encoder thread to write the remaining frames
     #[inline(always)]
-    pub fn flush(&mut self) {
-        self.iter.flush();
-
-        // to be dropped automatic
-        // self.composite = None;
-        // self.quad_iter = None;
-        // self.overlay = None;
-        // self.lib = None;
-        // self.masks = None;
-        // self.prev = None;
-        // self.next = None;
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Some((sender, encoder)) = self.encoder.take() {
+            drop(sender);
+            encoder
+                .join()
+                .map_err(|_| Error::WriteFailure("the encoder thread panicked".into()))??;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the frames on a thread and writes them on another, so that decoding and encoding
+/// overlap with the matching. The writer hands the frames it is done with back
+fn pipeline<I: FrameIter + 'static>(
+    input: String,
+    output: String,
+    config: &ProcessConfig,
+    lookahead: usize,
+) -> Result<(Pipeline, Vec<Warning>, i64, u32, u32), Error> {
+    let (mut iter, mut writer, frames, width, height) = I::new(input, output, config)?;
+    let warnings = iter.warnings().to_vec();
+
+    let (sender, decoded) = sync_channel(lookahead);
+    thread::spawn(move || {
+        let read = || loop {
+            match iter.next() {
+                Ok(Some(frame)) => {
+                    if sender.send(Ok(frame)).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    let _ = sender.send(Err(err));
+                    break;
+                }
+            }
+        };
+        if catch_unwind(AssertUnwindSafe(read)).is_err() {
+            let _ = sender.send(Err(Error::thread("the decoder thread panicked")));
+        }
+    });
+
+    let (sender, encoded) = sync_channel::<(RgbImage, Stamp)>(lookahead);
+    let (recycle, recycled) = channel();
+    let encoder = thread::spawn(move || {
+        for (img, stamp) in encoded {
+            writer.post_next(&img, stamp)?;
+            // the matcher may be gone already
+            let _ = recycle.send(img);
+        }
+        writer.flush()
+    });
+
+    Ok((
+        (decoded, (sender, encoder), recycled),
+        warnings,
+        frames,
+        width,
+        height,
+    ))
+}
+
+/// The mean color of the mask
+fn mean(img: &RgbImage, (x, y, w, h): Mask) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for j in y..(y + h) {
+        for i in x..(x + w) {
+            for (sum, &value) in sum.iter_mut().zip(img.get_pixel(i, j).0.iter()) {
+                *sum += u64::from(value);
+            }
+        }
+    }
+    let count = (u64::from(w) * u64::from(h)).max(1);
+    sum.map(|sum| ((sum + count / 2) / count) as u8)
+}
+
+/// The distance that is `share` of the one of black and white. The euclidean distances are
+/// squared, so that share is squared too
+#[inline(always)]
+fn relative_distance(share: f32, black_to_white: f32, squared: bool) -> f32 {
+    match squared {
+        true => share * share * black_to_white,
+        false => share * black_to_white,
     }
 }
 
@@ -484,17 +859,50 @@ impl fmt::Debug for ProcessWrapper {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct ProcessConfig {
     pub size: u16,
     pub k: u8,
     pub hamerly: bool,
+    pub seed: u64,
+    pub runs: u8,
+    pub converge: Option<f32>,
+    pub max_iter: Option<usize>,
     pub calc_unit: CalculationUnit,
     pub color_space: ColorSpace,
     pub dist_algo: DistanceAlgorithm,
+    /// The weights of the channels of the weighted euclidean distance, those of the cone for
+    /// HSV and of CIELAB for CIELCH
+    pub weights: [f32; 3],
+    pub cmc: (f32, f32),
     pub filter: Filter,
     pub quad_iter: Option<usize>,
     pub overlay: Option<u8>,
+    pub lookahead: u8,
+    pub threads: Option<usize>,
+    pub scaling: Scaling,
+    /// The histogram difference of two frames from which on every mask is filled again
+    pub scene_cut: f32,
+    /// The color difference of a mask, relative to the one of black and white,
+    /// from which on it is filled again
+    pub change_threshold: f32,
+    /// The section of the video to render, in seconds
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    /// Renders every `frame_step`-th frame of the section
+    pub frame_step: usize,
+    pub max_frames: Option<usize>,
+    pub audio: Audio,
+    /// Replaces the audio of the video
+    pub audio_file: Option<PathBuf>,
+    pub subtitles: bool,
+    /// Assumes 25 fps for the video streams that tell no frame rate, instead of failing
+    pub fallback_frame_rate: bool,
+    /// The Hamming distance of the perceptual hashes of two library images up to which the
+    /// one indexed later is left out as a near duplicate
+    pub dedup: Option<u32>,
+    /// Drops the library images that make poor tiles
+    pub quality: QualityFilter,
 }
 
 impl Default for ProcessConfig {
@@ -503,12 +911,33 @@ impl Default for ProcessConfig {
             size: 100,
             k: 1,
             hamerly: false,
+            seed: 0,
+            runs: 3,
+            converge: Default::default(),
+            max_iter: Default::default(),
             calc_unit: Default::default(),
             color_space: Default::default(),
             dist_algo: Default::default(),
+            weights: [1.; 3],
+            cmc: (2., 1.),
             filter: Default::default(),
             quad_iter: Default::default(),
             overlay: Default::default(),
+            lookahead: 4,
+            threads: Default::default(),
+            scaling: Default::default(),
+            scene_cut: 0.4,
+            change_threshold: 0.02,
+            start: Default::default(),
+            end: Default::default(),
+            frame_step: 1,
+            max_frames: Default::default(),
+            audio: Default::default(),
+            audio_file: Default::default(),
+            subtitles: true,
+            fallback_frame_rate: true,
+            dedup: Default::default(),
+            quality: Default::default(),
         }
     }
 }
@@ -540,12 +969,63 @@ impl From<Filter> for FilterType {
     }
 }
 
+/// The algorithm converting the video frames to RGB and back
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Scaling {
+    FastBilinear,
+    Bilinear,
+    Bicubic,
+    Lanczos,
+    Spline,
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Self::FastBilinear
+    }
+}
+
+impl From<Scaling> for Flags {
+    fn from(scaling: Scaling) -> Flags {
+        match scaling {
+            Scaling::FastBilinear => Flags::FAST_BILINEAR,
+            Scaling::Bilinear => Flags::BILINEAR,
+            Scaling::Bicubic => Flags::BICUBIC,
+            Scaling::Lanczos => Flags::LANCZOS,
+            Scaling::Spline => Flags::SPLINE,
+        }
+    }
+}
+
+/// What happens to the audio of the video
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Audio {
+    /// Copies the audio as it is
+    Keep,
+    Drop,
+    /// Re-encodes the audio to AAC
+    Aac,
+    /// Re-encodes the audio to Opus
+    Opus,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
-        crate::{ProcessWrapper, IMAGE_FILTER},
+        crate::{Error, Palette, ProcessWrapper, IMAGE_FILTER},
         async_std::task::block_on,
-        std::{fs::read_dir, path::PathBuf},
+        futures::StreamExt,
+        image::RgbImage,
+        std::{
+            fs::{create_dir_all, read, read_dir, remove_dir_all, remove_file},
+            path::PathBuf,
+        },
     };
 
     fn config() -> super::ProcessConfig {
@@ -553,17 +1033,38 @@ mod tests {
             size: 50,
             k: 1,
             hamerly: false,
+            seed: 0,
+            runs: 3,
+            converge: None,
+            max_iter: None,
             calc_unit: crate::CalculationUnit::Average,
             color_space: crate::ColorSpace::CIELAB,
             dist_algo: crate::DistanceAlgorithm::CIEDE2000,
+            weights: [1.; 3],
+            cmc: (2., 1.),
             filter: super::Filter::Nearest,
             quad_iter: None,
             overlay: Some(127),
+            lookahead: 4,
+            threads: None,
+            scaling: super::Scaling::FastBilinear,
+            scene_cut: 0.4,
+            change_threshold: 0.02,
+            start: None,
+            end: None,
+            frame_step: 1,
+            max_frames: None,
+            audio: super::Audio::Keep,
+            audio_file: None,
+            subtitles: true,
+            fallback_frame_rate: true,
+            dedup: Some(4),
+            quality: Default::default(),
         }
     }
 
     fn lib() -> Vec<PathBuf> {
-        read_dir("../crawler/test")
+        let mut lib = read_dir("../crawler/test")
             .unwrap()
             .flatten()
             .filter_map(|entry| {
@@ -578,31 +1079,53 @@ mod tests {
                 }
                 None
             })
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+        lib.sort();
+        lib
     }
 
-    fn process(mut proc: ProcessWrapper) {
-        let library = lib();
+    /// Solid images of distinct colors
+    fn solid_lib(folder: &str, len: u8) -> Vec<PathBuf> {
+        create_dir_all(folder).unwrap();
+        (0..len)
+            .map(|i| {
+                let path = PathBuf::from(format!("{}/{}.png", folder, i));
+                let v = i * (u8::MAX / len);
+                RgbImage::from_pixel(8, 8, [v, u8::MAX - v, v / 2].into())
+                    .save(&path)
+                    .unwrap();
+                path
+            })
+            .collect()
+    }
 
+    /// In the order the chunks finish, put back in the order of the library
+    async fn index(proc: &ProcessWrapper, library: Vec<PathBuf>) -> (Vec<Palette>, Vec<RgbImage>) {
+        let mut chunks = proc
+            .index(library)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        chunks.sort_unstable_by_key(|(idx, _)| *idx);
+        chunks
+            .into_iter()
+            .flat_map(|(_, items)| items.into_iter().flatten())
+            .unzip()
+    }
+
+    fn process(mut proc: ProcessWrapper, library: Vec<PathBuf>) {
         block_on(async move {
-            let mut lib_color = Vec::with_capacity(library.len());
-            let mut lib_image = Vec::with_capacity(library.len());
-            let tasks = proc.index(library);
-            for task in tasks {
-                if let Some((color, image)) = task.await {
-                    lib_color.push(color);
-                    lib_image.push(image);
-                }
-            }
-            proc.post_index(lib_color, lib_image);
+            let (lib_color, lib_image) = index(&proc, library).await;
+            proc.post_index(lib_color, lib_image).unwrap();
 
-            while proc.pre_fill() {
+            while proc.pre_fill().unwrap() {
                 let tasks = proc.fill();
                 for task in tasks {
-                    let (mask, replace_idx) = task.await;
-                    proc.post_fill_step(mask, replace_idx);
+                    for (mask, replace_idx) in task.await.unwrap() {
+                        proc.post_fill_step(mask, replace_idx);
+                    }
                 }
-                proc.post_fill();
+                proc.post_fill().unwrap();
             }
         });
     }
@@ -615,9 +1138,172 @@ mod tests {
             "../static/images/testdata.jpg".to_string(),
             "test.png".to_string(),
             false,
+        )
+        .unwrap();
+
+        process(proc, lib());
+    }
+
+    #[test]
+    fn library_order() {
+        let library = solid_lib("test-order", 32);
+        let proc = super::ProcessWrapper::new(
+            super::ProcessConfig {
+                threads: Some(4),
+                dedup: None,
+                ..config()
+            },
+            "../static/images/testdata.jpg".to_string(),
+            "test-order.png".to_string(),
+            false,
+        )
+        .unwrap();
+        let (_, lib_image) = block_on(index(&proc, library));
+        remove_dir_all("test-order").unwrap();
+
+        let firsts = lib_image
+            .iter()
+            .map(|img| img.get_pixel(0, 0).0[0])
+            .collect::<Vec<_>>();
+        let expected = (0..32).map(|i| i * (u8::MAX / 32)).collect::<Vec<_>>();
+        assert_eq!(firsts, expected);
+    }
+
+    #[test]
+    fn same_seed() {
+        let mut library = solid_lib("test-seed", 16);
+        library.extend(lib());
+        let run = |output: &str| {
+            let proc = super::ProcessWrapper::new(
+                super::ProcessConfig {
+                    calc_unit: crate::CalculationUnit::KMeans,
+                    k: 2,
+                    seed: 7,
+                    threads: Some(4),
+                    dedup: None,
+                    ..config()
+                },
+                "../static/images/testdata.jpg".to_string(),
+                output.to_string(),
+                false,
+            )
+            .unwrap();
+            process(proc, library.clone());
+            let bytes = read(output).unwrap();
+            remove_file(output).unwrap();
+            bytes
+        };
+        let first = run("test-seed-a.png");
+        let second = run("test-seed-b.png");
+        remove_dir_all("test-seed").unwrap();
+
+        assert!(first == second);
+    }
+
+    #[test]
+    fn missing_frame() {
+        let mut proc = super::ProcessWrapper::new(
+            config(),
+            "../static/images/testdata.jpg".to_string(),
+            "test-missing.png".to_string(),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(proc.post_fill(), Err(Error::MissingFrame));
+    }
+
+    #[test]
+    fn euclidean_threshold() {
+        let proc = |change_threshold| {
+            super::ProcessWrapper::new(
+                super::ProcessConfig {
+                    color_space: crate::ColorSpace::RGB,
+                    dist_algo: crate::DistanceAlgorithm::Euclidean,
+                    change_threshold,
+                    ..config()
+                },
+                "../static/images/testdata.jpg".to_string(),
+                "test-threshold.png".to_string(),
+                false,
+            )
+            .unwrap()
+        };
+        // 5% of the way from black to white
+        let changed = |proc: &ProcessWrapper| {
+            let a = proc.inner.convert(&[100; 3]);
+            let b = proc.inner.convert(&[113; 3]);
+            proc.inner.distance(&a, &b) > proc.change_threshold
+        };
+
+        assert!(changed(&proc(0.04)));
+        assert!(!changed(&proc(0.06)));
+    }
+
+    #[test]
+    fn equal_weights() {
+        let proc = |dist_algo| {
+            super::ProcessWrapper::new(
+                super::ProcessConfig {
+                    color_space: crate::ColorSpace::HSV,
+                    dist_algo,
+                    ..config()
+                },
+                "../static/images/testdata.jpg".to_string(),
+                "test-weights.png".to_string(),
+                false,
+            )
+            .unwrap()
+        };
+        let weighted = proc(crate::DistanceAlgorithm::WeightedEuclidean);
+        let euclidean = proc(crate::DistanceAlgorithm::Euclidean);
+        let pairs = [
+            ([200, 30, 30], [30, 30, 200]),
+            ([250, 10, 10], [10, 10, 250]),
+            ([40, 40, 40], [45, 35, 40]),
+        ];
+
+        for (a, b) in pairs {
+            let (a, b) = (weighted.inner.convert(&a), weighted.inner.convert(&b));
+            let distance = weighted.inner.distance(&a, &b);
+            assert!((distance - euclidean.inner.distance(&a, &b)).abs() < 1e-5);
+        }
+        // the hue of the dark colors hardly matters
+        let dark = weighted.inner.distance(
+            &weighted.inner.convert(&[40, 20, 20]),
+            &weighted.inner.convert(&[20, 20, 40]),
+        );
+        let bright = weighted.inner.distance(
+            &weighted.inner.convert(&[250, 10, 10]),
+            &weighted.inner.convert(&[10, 10, 250]),
+        );
+        assert!(dark < bright / 10.);
+    }
+
+    #[test]
+    fn panicked_job() {
+        let proc = super::ProcessWrapper::new(
+            config(),
+            "../static/images/testdata.jpg".to_string(),
+            "test-panic.png".to_string(),
+            false,
+        )
+        .unwrap();
+        let job = proc.spawn(|| panic!("job"));
+
+        assert!(matches!(block_on(job), Err(Error::ThreadFailure(_))));
+    }
+
+    #[test]
+    fn missing_input() {
+        let proc = super::ProcessWrapper::new(
+            config(),
+            "../static/images/missing.jpg".to_string(),
+            "test.png".to_string(),
+            false,
         );
 
-        process(proc);
+        assert!(matches!(proc, Err(Error::UnsupportedFormat(_))));
     }
 
     #[test]
@@ -631,8 +1317,9 @@ mod tests {
             "../static/videos/testdata.mp4".to_string(),
             "test.mp4".to_string(),
             true,
-        );
+        )
+        .unwrap();
 
-        process(proc);
+        process(proc, lib());
     }
 }
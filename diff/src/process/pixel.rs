@@ -1,7 +1,7 @@
 use {
-    super::{impl_process, Converter, Distance, LibItem, Mask},
-    crate::utils::RawColor,
-    image::{self, imageops::FilterType, Pixel, RgbImage},
+    super::{impl_process, Converter, Distance, Frame, LibItem, Mask},
+    crate::utils::{ColorImage, Palette},
+    image::{self, imageops::FilterType, RgbImage},
 };
 
 pub(super) struct PixelImpl {
@@ -9,24 +9,37 @@ pub(super) struct PixelImpl {
     filter: FilterType,
     converter: Converter,
     distance: Distance,
-    lib_color: Box<[RawColor]>,
+    lib_color: Box<[Palette]>,
     lib_image: Box<[RgbImage]>,
-    prev: Option<RgbImage>,
-    next: Option<RgbImage>,
+    lib_converted: Box<[ColorImage]>,
 }
 
 impl_process!(PixelImpl;
     #[inline(always)]
     fn index_step(&self, img: RgbImage) -> LibItem {
-        (RawColor::default(), img)
+        (Palette::default(), img)
     }
 
     #[inline(always)]
-    fn fill_step(&self, mask: Mask) -> (Mask, usize) {
-        let img = self.next.as_ref().unwrap();
-        let (idx, _) = self
+    fn post_index_step(&mut self) {
+        self.lib_converted = self
             .lib_image
             .iter()
+            .map(|img| ColorImage::new(img, &self.converter))
+            .collect();
+    }
+
+    #[inline(always)]
+    fn pre_fill_step(&self, frame: &mut Frame) {
+        frame.converted = Some(ColorImage::new(&frame.img, &self.converter));
+    }
+
+    #[inline(always)]
+    fn fill_step(&self, frame: &Frame, mask: Mask) -> (Mask, usize) {
+        let img = frame.converted.as_ref().unwrap();
+        let (idx, _) = self
+            .lib_converted
+            .iter()
             .enumerate()
             .min_by(|(_, a), (_, b)| {
                 self.compare(img, a, mask)
@@ -54,26 +67,18 @@ impl PixelImpl {
             distance,
             lib_color: Vec::new().into_boxed_slice(),
             lib_image: Vec::new().into_boxed_slice(),
-            prev: None,
-            next: None,
+            lib_converted: Vec::new().into_boxed_slice(),
         }
     }
 
     // #[inline(always)]
-    fn compare(&self, img: &RgbImage, other: &RgbImage, (x, y, w, h): Mask) -> f32 {
-        let Self {
-            converter,
-            distance,
-            ..
-        } = self;
+    fn compare(&self, img: &ColorImage, other: &ColorImage, (x, y, w, h): Mask) -> f32 {
+        let Self { distance, .. } = self;
 
         let mut ans = 0f32;
         for j in 0..h {
             for i in 0..w {
-                ans += distance(
-                    &converter(img.get_pixel(i + x, j + y).channels()),
-                    &converter(other.get_pixel(i + x, j + y).channels()),
-                );
+                ans += distance(img.get(i + x, j + y), other.get(i, j));
             }
         }
         ans
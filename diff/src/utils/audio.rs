@@ -0,0 +1,474 @@
+use {
+    crate::Error,
+    ffmpeg::{
+        codec, decoder, encoder, ffi, filter, format, frame, media, ChannelLayout, Packet,
+        Rational, Rescale,
+    },
+    std::path::Path,
+};
+
+/// Used when the source gives no bit rate, or a much higher one such as PCM
+const BIT_RATE: usize = 128_000;
+
+/// Whether the output container can hold the codec, an unknown answer counts as yes
+pub(super) fn supports(octx: &format::context::Output, id: codec::Id) -> bool {
+    unsafe {
+        ffi::avformat_query_codec(
+            octx.format().as_ptr(),
+            id.into(),
+            ffi::FF_COMPLIANCE_NORMAL as _,
+        ) != 0
+    }
+}
+
+/// Adds a stream that copies the packets of `ist` as they are
+pub(super) fn copy_stream(
+    ist: &format::stream::Stream,
+    octx: &mut format::context::Output,
+) -> Result<(), Error> {
+    let id = ist.codec().id();
+    if !supports(octx, id) {
+        return Err(Error::UnsupportedFormat(format!(
+            "the output cannot hold {:?} as it is, drop or re-encode it",
+            id
+        )));
+    }
+    let mut ost = octx
+        .add_stream(encoder::find(codec::Id::None))
+        .map_err(Error::write)?;
+    ost.set_parameters(ist.parameters());
+    unsafe {
+        (*ost.parameters().as_mut_ptr()).codec_tag = 0;
+    }
+    Ok(())
+}
+
+#[inline(always)]
+pub(super) fn copy_packet(
+    mut packet: Packet,
+    ist_time_base: Rational,
+    ost_time_base: Rational,
+    ost_index: usize,
+    octx: &mut format::context::Output,
+) -> Result<(), Error> {
+    packet.rescale_ts(ist_time_base, ost_time_base);
+    packet.set_position(-1);
+    packet.set_stream(ost_index);
+    packet.write_interleaved(octx).map_err(Error::write)
+}
+
+/// Decodes an audio stream and encodes it again, the samples are converted
+/// by a filter graph to the format, rate, layout and frame size of the encoder
+pub(super) struct AudioTranscoder {
+    ost_index: usize,
+    decoder: decoder::Audio,
+    encoder: encoder::audio::Encoder,
+    filter: filter::Graph,
+    layout: ChannelLayout,
+    filter_time_base: Rational,
+    encoder_time_base: Rational,
+}
+
+impl AudioTranscoder {
+    pub(super) fn new(
+        ist: &format::stream::Stream,
+        octx: &mut format::context::Output,
+        ost_index: usize,
+        id: codec::Id,
+    ) -> Result<Self, Error> {
+        if !supports(octx, id) {
+            return Err(Error::UnsupportedFormat(format!(
+                "the output cannot hold {:?}",
+                id
+            )));
+        }
+        let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+        let decoder = ist.codec().decoder().audio().map_err(Error::unsupported)?;
+        // the native Opus encoder is experimental
+        let codec = match id {
+            codec::Id::OPUS => encoder::find_by_name("libopus").or_else(|| encoder::find(id)),
+            _ => encoder::find(id),
+        }
+        .ok_or_else(|| Error::EncoderUnavailable(format!("{:?}", id)))?
+        .audio()
+        .map_err(Error::encoder)?;
+
+        let layout = match decoder.channel_layout().is_empty() {
+            true => ChannelLayout::default(decoder.channels() as _),
+            false => decoder.channel_layout(),
+        };
+        let rate = decoder.rate() as i32;
+        let rate = match codec.rates() {
+            Some(rates) => {
+                let rates = rates.collect::<Vec<_>>();
+                match rates.contains(&rate) {
+                    true => rate,
+                    false => rates.into_iter().max().unwrap_or(rate),
+                }
+            }
+            None => rate,
+        };
+        let channel_layout = codec
+            .channel_layouts()
+            .map(|layouts| layouts.best(layout.channels()))
+            .unwrap_or(ChannelLayout::STEREO);
+        let format = codec
+            .formats()
+            .and_then(|mut formats| formats.next())
+            .unwrap_or_else(|| decoder.format());
+
+        let mut ost = octx.add_stream(codec).map_err(Error::encoder)?;
+        let mut encoder = ost.codec().encoder().audio().map_err(Error::encoder)?;
+        if global_header {
+            encoder.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+        encoder.set_rate(rate);
+        encoder.set_channel_layout(channel_layout);
+        encoder.set_channels(channel_layout.channels());
+        encoder.set_format(format);
+        encoder.set_bit_rate(match decoder.bit_rate() {
+            0 => BIT_RATE,
+            bit_rate => bit_rate.min(BIT_RATE * 2),
+        });
+        encoder.set_time_base((1, rate));
+        ost.set_time_base((1, rate));
+        let encoder = encoder.open_as(codec).map_err(Error::encoder)?;
+        ost.set_parameters(&encoder);
+
+        let filter = Self::filter(ist.time_base(), &decoder, layout, &encoder, codec)
+            .map_err(Error::encoder)?;
+        let filter_time_base = unsafe {
+            Rational::from(ffi::av_buffersink_get_time_base(
+                filter.get("out").unwrap().as_ptr(),
+            ))
+        };
+
+        Ok(Self {
+            ost_index,
+            decoder,
+            encoder,
+            filter,
+            layout,
+            filter_time_base,
+            encoder_time_base: Rational(1, rate),
+        })
+    }
+
+    fn filter(
+        time_base: Rational,
+        decoder: &decoder::Audio,
+        layout: ChannelLayout,
+        encoder: &encoder::audio::Encoder,
+        codec: codec::Audio,
+    ) -> Result<filter::Graph, ffmpeg::Error> {
+        let mut graph = filter::Graph::new();
+        let args = format!(
+            "time_base={}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+            time_base,
+            decoder.rate(),
+            decoder.format().name(),
+            layout.bits()
+        );
+        graph.add(&filter::find("abuffer").unwrap(), "in", &args)?;
+        graph.add(&filter::find("abuffersink").unwrap(), "out", "")?;
+        {
+            let mut out = graph.get("out").unwrap();
+            out.set_sample_format(encoder.format());
+            out.set_channel_layout(encoder.channel_layout());
+            out.set_sample_rate(encoder.rate());
+        }
+        graph.output("in", 0)?.input("out", 0)?.parse("anull")?;
+        graph.validate()?;
+
+        if !codec
+            .capabilities()
+            .contains(codec::capabilities::Capabilities::VARIABLE_FRAME_SIZE)
+        {
+            graph
+                .get("out")
+                .unwrap()
+                .sink()
+                .set_frame_size(encoder.frame_size());
+        }
+        Ok(graph)
+    }
+
+    /// The timestamps of the packet are in the time base of the input stream
+    pub(super) fn send_packet(
+        &mut self,
+        packet: &Packet,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<(), Error> {
+        // a corrupt packet is skipped, as for the video
+        if self.decoder.send_packet(packet).is_err() {
+            return Ok(());
+        }
+        self.receive_and_process_decoded_frames(octx, ost_time_base)
+    }
+
+    pub(super) fn flush(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<(), Error> {
+        let _ = self.decoder.send_eof();
+        self.receive_and_process_decoded_frames(octx, ost_time_base)?;
+        self.filter
+            .get("in")
+            .unwrap()
+            .source()
+            .flush()
+            .map_err(Error::write)?;
+        self.receive_and_process_filtered_frames(octx, ost_time_base)?;
+        self.encoder.send_eof().map_err(Error::write)?;
+        self.receive_and_process_encoded_packets(octx, ost_time_base)
+    }
+
+    fn receive_and_process_decoded_frames(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<(), Error> {
+        let mut decoded = frame::Audio::empty();
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            let timestamp = decoded.timestamp();
+            decoded.set_pts(timestamp);
+            decoded.set_channel_layout(self.layout);
+            self.filter
+                .get("in")
+                .unwrap()
+                .source()
+                .add(&decoded)
+                .map_err(Error::write)?;
+            self.receive_and_process_filtered_frames(octx, ost_time_base)?;
+        }
+        Ok(())
+    }
+
+    fn receive_and_process_filtered_frames(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<(), Error> {
+        let mut filtered = frame::Audio::empty();
+        while self
+            .filter
+            .get("out")
+            .unwrap()
+            .sink()
+            .frame(&mut filtered)
+            .is_ok()
+        {
+            let pts = filtered
+                .pts()
+                .map(|pts| pts.rescale(self.filter_time_base, self.encoder_time_base));
+            filtered.set_pts(pts);
+            self.encoder.send_frame(&filtered).map_err(Error::write)?;
+            self.receive_and_process_encoded_packets(octx, ost_time_base)?;
+        }
+        Ok(())
+    }
+
+    fn receive_and_process_encoded_packets(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<(), Error> {
+        let mut encoded = Packet::empty();
+        while self.encoder.receive_packet(&mut encoded).is_ok() {
+            encoded.set_stream(self.ost_index);
+            encoded.rescale_ts(self.encoder_time_base, ost_time_base);
+            encoded.write_interleaved(octx).map_err(Error::write)?;
+        }
+        Ok(())
+    }
+}
+
+/// The audio of another file, replacing the one of the input
+pub(super) struct ExternalAudio {
+    ictx: format::context::Input,
+    ist_index: usize,
+    ist_time_base: Rational,
+    pub(super) ost_index: usize,
+    /// Copies the packets if there is none
+    transcoder: Option<AudioTranscoder>,
+    /// The first packet after the time written so far
+    pending: Option<Packet>,
+}
+
+impl ExternalAudio {
+    pub(super) fn new(
+        path: &Path,
+        octx: &mut format::context::Output,
+        ost_index: usize,
+        id: Option<codec::Id>,
+    ) -> Result<Self, Error> {
+        let ictx = format::input(&path).map_err(Error::unsupported)?;
+        let (ist_index, ist_time_base, transcoder) = {
+            let ist = ictx.streams().best(media::Type::Audio).ok_or_else(|| {
+                Error::UnsupportedFormat(format!("no audio stream in {}", path.display()))
+            })?;
+            let transcoder = match id {
+                Some(id) => Some(AudioTranscoder::new(&ist, octx, ost_index, id)?),
+                None => {
+                    copy_stream(&ist, octx)?;
+                    None
+                }
+            };
+            (ist.index(), ist.time_base(), transcoder)
+        };
+        Ok(Self {
+            ictx,
+            ist_index,
+            ist_time_base,
+            ost_index,
+            transcoder,
+            pending: None,
+        })
+    }
+
+    /// Writes the packets up to `time`, in seconds
+    pub(super) fn write_until(
+        &mut self,
+        time: f64,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<(), Error> {
+        loop {
+            let packet = match self.pending.take().or_else(|| self.next_packet()) {
+                Some(packet) => packet,
+                None => return Ok(()),
+            };
+            if let Some(pts) = packet.pts() {
+                if pts as f64 * f64::from(self.ist_time_base) > time {
+                    self.pending = Some(packet);
+                    return Ok(());
+                }
+            }
+            match &mut self.transcoder {
+                Some(transcoder) => transcoder.send_packet(&packet, octx, ost_time_base)?,
+                None => copy_packet(
+                    packet,
+                    self.ist_time_base,
+                    ost_time_base,
+                    self.ost_index,
+                    octx,
+                )?,
+            }
+        }
+    }
+
+    pub(super) fn flush(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<(), Error> {
+        match &mut self.transcoder {
+            Some(transcoder) => transcoder.flush(octx, ost_time_base),
+            None => Ok(()),
+        }
+    }
+
+    fn next_packet(&mut self) -> Option<Packet> {
+        let ist_index = self.ist_index;
+        self.ictx
+            .packets()
+            .find(|(stream, _)| stream.index() == ist_index)
+            .map(|(_, packet)| packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{supports, ExternalAudio},
+        ffmpeg::{codec, format, media, rescale},
+        std::{
+            f32::consts::TAU,
+            fs::{remove_file, write},
+            path::Path,
+        },
+    };
+
+    /// A mono 16-bit PCM sine
+    fn write_wav(path: &str, rate: u32, seconds: u32) {
+        let samples = rate * seconds;
+        let mut data = Vec::with_capacity(44 + samples as usize * 2);
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + samples * 2).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, 1 channel
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&rate.to_le_bytes());
+        data.extend_from_slice(&(rate * 2).to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(samples * 2).to_le_bytes());
+        for i in 0..samples {
+            let value = (i as f32 * 440. * TAU / rate as f32).sin() * 8000.;
+            data.extend_from_slice(&(value as i16).to_le_bytes());
+        }
+        write(path, data).unwrap();
+    }
+
+    /// Writes the audio of `input` alone into `output`, returns its codec and duration
+    fn round_trip(input: &str, output: &str, id: Option<codec::Id>) -> (codec::Id, f64) {
+        {
+            let mut octx = format::output(&output).unwrap();
+            let mut audio = ExternalAudio::new(Path::new(input), &mut octx, 0, id).unwrap();
+            octx.write_header().unwrap();
+            let time_base = octx.stream(0).unwrap().time_base();
+            audio.write_until(f64::MAX, &mut octx, time_base).unwrap();
+            audio.flush(&mut octx, time_base).unwrap();
+            octx.write_trailer().unwrap();
+        }
+        let ictx = format::input(&output).unwrap();
+        let id = ictx
+            .streams()
+            .best(media::Type::Audio)
+            .unwrap()
+            .codec()
+            .id();
+        let seconds = ictx.duration() as f64 * f64::from(rescale::TIME_BASE);
+        remove_file(output).unwrap();
+        (id, seconds)
+    }
+
+    #[test]
+    fn container_support() {
+        crate::init();
+        let output = "test-supports.mp4";
+        let mp4 = format::output(&output).unwrap();
+        let mp4_support =
+            [codec::Id::H264, codec::Id::AAC, codec::Id::SUBRIP].map(|id| supports(&mp4, id));
+        drop(mp4);
+        let output = "test-supports.mkv";
+        let mkv = format::output(&output).unwrap();
+        let mkv_support = supports(&mkv, codec::Id::SUBRIP);
+        drop(mkv);
+        let _ = remove_file("test-supports.mp4");
+        let _ = remove_file("test-supports.mkv");
+
+        assert_eq!(mp4_support, [true, true, false]);
+        assert!(mkv_support);
+    }
+
+    #[test]
+    fn transcode() {
+        crate::init();
+        let input = "test-audio.wav";
+        write_wav(input, 44_100, 1);
+        let aac = round_trip(input, "test-audio.mp4", Some(codec::Id::AAC));
+        let copy = round_trip(input, "test-audio.mkv", None);
+        remove_file(input).unwrap();
+
+        assert_eq!(aac.0, codec::Id::AAC);
+        assert!((aac.1 - 1.).abs() < 0.1, "{}", aac.1);
+        assert_eq!(copy.0, codec::Id::PCM_S16LE);
+        assert!((copy.1 - 1.).abs() < 0.1, "{}", copy.1);
+    }
+}
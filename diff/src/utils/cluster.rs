@@ -0,0 +1,281 @@
+use {
+    super::{lab_to_lch, lch_to_lab, Color, MyLch, MyOklab, MySrgb, MyXyz, RawColor, YCbCr},
+    kmeans_colors::{Calculate, CentroidData, Hamerly, HamerlyCentroids, HamerlyPoint, Sort},
+    palette::IntoColor,
+    rand::Rng,
+    std::marker::PhantomData,
+};
+
+/// The spaces kmeans_colors does not support, clustered as `Point`s
+pub trait Coords: Color {
+    /// Maps the hue to cartesian coordinates, so that it wraps around
+    #[inline(always)]
+    fn to_coords(raw: &RawColor) -> RawColor {
+        *raw
+    }
+
+    #[inline(always)]
+    fn from_coords(coords: &RawColor) -> RawColor {
+        *coords
+    }
+}
+
+impl Coords for MyLch {
+    #[inline(always)]
+    fn to_coords(raw: &RawColor) -> RawColor {
+        lch_to_lab(raw)
+    }
+
+    #[inline(always)]
+    fn from_coords(coords: &RawColor) -> RawColor {
+        lab_to_lch(coords)
+    }
+}
+
+impl Coords for MyOklab {}
+impl Coords for MyXyz {}
+impl Coords for YCbCr {}
+
+/// A color of the space `T`, by the coordinates in which it is averaged
+#[derive(Clone, Copy)]
+pub struct Point<T> {
+    coords: RawColor,
+    space: PhantomData<T>,
+}
+
+impl<T: Coords> Point<T> {
+    #[inline(always)]
+    pub(crate) fn new(color: T) -> Self {
+        Self {
+            coords: T::to_coords(&color.into_raw()),
+            space: PhantomData,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn color(&self) -> T {
+        *T::from_raw(&T::from_coords(&self.coords))
+    }
+
+    /// The mean of the points, none if there is no point
+    fn mean<'a>(points: impl Iterator<Item = &'a Self>) -> Option<Self>
+    where
+        T: 'a,
+    {
+        let mut count = 0.;
+        let mut coords = [0.; 3];
+        for point in points {
+            count += 1.;
+            coords
+                .iter_mut()
+                .zip(point.coords)
+                .for_each(|(c, p)| *c += p);
+        }
+        if count == 0. {
+            return None;
+        }
+        Some(Self {
+            coords: coords.map(|c| c / count),
+            space: PhantomData,
+        })
+    }
+}
+
+impl<T: Coords> Calculate for Point<T> {
+    fn get_closest_centroid(buffer: &[Self], centroids: &[Self], indices: &mut Vec<u8>) {
+        for point in buffer {
+            let (idx, _) = centroids
+                .iter()
+                .map(|centroid| Self::difference(point, centroid))
+                .enumerate()
+                .fold((0, f32::MAX), |min, diff| match diff.1 < min.1 {
+                    true => diff,
+                    false => min,
+                });
+            indices.push(idx as u8);
+        }
+    }
+
+    fn recalculate_centroids(
+        rng: &mut impl Rng,
+        buf: &[Self],
+        centroids: &mut [Self],
+        indices: &[u8],
+    ) {
+        for (idx, centroid) in centroids.iter_mut().enumerate() {
+            let members = buf
+                .iter()
+                .zip(indices)
+                .filter(|(_, &i)| i as usize == idx)
+                .map(|(point, _)| point);
+            *centroid = Self::mean(members).unwrap_or_else(|| Self::create_random(rng));
+        }
+    }
+
+    fn check_loop(centroids: &[Self], old_centroids: &[Self]) -> f32 {
+        let mut sum = [0.; 3];
+        for (centroid, old) in centroids.iter().zip(old_centroids) {
+            for ((s, c), o) in sum.iter_mut().zip(centroid.coords).zip(old.coords) {
+                *s += c - o;
+            }
+        }
+        sum.iter().map(|s| s * s).sum()
+    }
+
+    /// A random color within the sRGB gamut
+    fn create_random(rng: &mut impl Rng) -> Self {
+        let rgb = MySrgb::new(rng.gen(), rng.gen(), rng.gen());
+        Self::new(rgb.into_color())
+    }
+
+    /// The squared euclidean distance
+    #[inline(always)]
+    fn difference(c1: &Self, c2: &Self) -> f32 {
+        c1.coords
+            .iter()
+            .zip(c2.coords)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum()
+    }
+}
+
+impl<T: Coords> Hamerly for Point<T> {
+    fn compute_half_distances(centers: &mut HamerlyCentroids<Self>) {
+        let HamerlyCentroids {
+            centroids,
+            half_distances,
+            ..
+        } = centers;
+        for (i, half_distance) in half_distances.iter_mut().enumerate() {
+            let min = centroids
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| i != *j)
+                .map(|(_, other)| Self::difference(&centroids[i], other))
+                .fold(f32::MAX, f32::min);
+            *half_distance = min.sqrt() * 0.5;
+        }
+    }
+
+    fn get_closest_centroid_hamerly(
+        buffer: &[Self],
+        centers: &HamerlyCentroids<Self>,
+        points: &mut [HamerlyPoint],
+    ) {
+        let HamerlyCentroids {
+            centroids,
+            half_distances,
+            ..
+        } = centers;
+        for (value, point) in buffer.iter().zip(points) {
+            let idx = point.index as usize;
+            let z = half_distances[idx].max(point.lower_bound);
+            if point.upper_bound <= z {
+                continue;
+            }
+            point.upper_bound = Self::difference(value, &centroids[idx]).sqrt();
+            if point.upper_bound <= z || centroids.len() < 2 {
+                continue;
+            }
+
+            // the two closest centroids
+            let (mut closest, mut min1, mut min2) = (0, f32::MAX, f32::MAX);
+            for (j, centroid) in centroids.iter().enumerate() {
+                let diff = Self::difference(value, centroid);
+                if diff < min1 {
+                    min2 = min1;
+                    min1 = diff;
+                    closest = j;
+                } else if diff < min2 {
+                    min2 = diff;
+                }
+            }
+            if closest != idx {
+                point.index = closest as u8;
+                point.upper_bound = min1.sqrt();
+            }
+            point.lower_bound = min2.sqrt();
+        }
+    }
+
+    fn recalculate_centroids_hamerly(
+        rng: &mut impl Rng,
+        buf: &[Self],
+        centers: &mut HamerlyCentroids<Self>,
+        points: &[HamerlyPoint],
+    ) {
+        let HamerlyCentroids {
+            centroids, deltas, ..
+        } = centers;
+        for (idx, (centroid, delta)) in centroids.iter_mut().zip(deltas).enumerate() {
+            let members = buf
+                .iter()
+                .zip(points)
+                .filter(|(_, point)| point.index as usize == idx)
+                .map(|(value, _)| value);
+            let new = Self::mean(members).unwrap_or_else(|| Self::create_random(rng));
+            *delta = Self::difference(centroid, &new).sqrt();
+            *centroid = new;
+        }
+    }
+
+    fn update_bounds(centers: &HamerlyCentroids<Self>, points: &mut [HamerlyPoint]) {
+        let max = centers.deltas.iter().copied().fold(0., f32::max);
+        for point in points {
+            point.upper_bound += centers.deltas[point.index as usize];
+            point.lower_bound -= max;
+        }
+    }
+}
+
+impl<T: Coords> Sort for Point<T> {
+    fn get_dominant_color(data: &[CentroidData<Self>]) -> Option<Self> {
+        data.iter()
+            .max_by(|a, b| a.percentage.partial_cmp(&b.percentage).unwrap())
+            .map(|data| data.centroid)
+    }
+
+    /// In the order of the centroids, each one with the share of the points closest to it
+    #[allow(clippy::ptr_arg)]
+    fn sort_indexed_colors(centroids: &Vec<Self>, indices: &[u8]) -> Vec<CentroidData<Self>> {
+        let mut counts = vec![0usize; centroids.len()];
+        for &idx in indices {
+            counts[idx as usize] += 1;
+        }
+        let len = indices.len().max(1) as f32;
+        centroids
+            .iter()
+            .zip(counts)
+            .enumerate()
+            .map(|(idx, (&centroid, count))| CentroidData {
+                centroid,
+                percentage: count as f32 / len,
+                index: idx as u8,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::Point,
+        crate::MyLch,
+        kmeans_colors::Calculate,
+        rand::{rngs::StdRng, SeedableRng},
+    };
+
+    #[test]
+    fn hue_wrap_around() {
+        let red = |hue| Point::new(MyLch::new(50., 40., hue));
+        let buf = [red(350.), red(10.)];
+        let mut centroids = [red(180.)];
+        let mut rng = StdRng::seed_from_u64(0);
+        Point::recalculate_centroids(&mut rng, &buf, &mut centroids, &[0, 0]);
+
+        // the mean of red hues is still red
+        let hue = centroids[0].color().hue.to_positive_degrees();
+        assert!(hue.min(360. - hue) < 1e-3);
+        assert!(Point::difference(&buf[0], &buf[1]) < Point::difference(&buf[0], &red(90.)));
+    }
+}
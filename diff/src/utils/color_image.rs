@@ -0,0 +1,38 @@
+use {
+    super::RawColor,
+    image::{Pixel, RgbImage},
+};
+
+/// An image converted to the working color space, pixel by pixel
+pub(crate) struct ColorImage {
+    width: u32,
+    data: Box<[RawColor]>,
+}
+
+impl ColorImage {
+    pub(crate) fn new(img: &RgbImage, converter: impl Fn(&[u8]) -> RawColor) -> Self {
+        Self {
+            width: img.width(),
+            data: img.pixels().map(|p| converter(p.channels())).collect(),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn get(&self, x: u32, y: u32) -> &RawColor {
+        &self.data[y as usize * self.width as usize + x as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::ColorImage, image::RgbImage};
+
+    #[test]
+    fn row_major() {
+        let img = RgbImage::from_fn(3, 2, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        let converted = ColorImage::new(&img, |p| [p[0] as f32, p[1] as f32, p[2] as f32]);
+
+        assert_eq!(converted.get(2, 1), &[2., 1., 0.]);
+        assert_eq!(converted.get(0, 1), &[0., 1., 0.]);
+    }
+}
@@ -0,0 +1,29 @@
+/// Keeps the hash unless a kept one is within `threshold` bits, returns whether it is kept
+pub(crate) fn keep_unique(kept: &mut Vec<u64>, hash: u64, threshold: u32) -> bool {
+    let unique = kept
+        .iter()
+        .all(|&other| (hash ^ other).count_ones() > threshold);
+    if unique {
+        kept.push(hash);
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::keep_unique;
+
+    #[test]
+    fn grouping() {
+        let hashes = [0b0000, 0b0011, 0b0111, 0xff00, 0xff01];
+        let mut kept = Vec::new();
+        // only the kept hashes count, the third is close to the dropped second one
+        let keep = hashes.map(|hash| keep_unique(&mut kept, hash, 2));
+        assert_eq!(keep, [true, false, true, true, false]);
+        assert_eq!(kept, [0b0000, 0b0111, 0xff00]);
+
+        let mut kept = Vec::new();
+        let keep = [1, 1, 3].map(|hash| keep_unique(&mut kept, hash, 0));
+        assert_eq!(keep, [true, false, true]);
+    }
+}
@@ -0,0 +1,119 @@
+use {
+    super::{MyLab, RawColor},
+    palette::{convert::IntoColorUnclamped, ColorDifference, Pixel},
+};
+
+/// The differences are computed in CIE L*a*b*, whatever the working color space is.
+/// The colors out of its range are not clamped, so that they keep their distance
+/// See [Color difference](https://en.wikipedia.org/wiki/Color_difference)
+#[inline(always)]
+fn to_lab<T: Copy + Pixel<f32> + IntoColorUnclamped<MyLab>>(raw: &RawColor) -> MyLab {
+    (*T::from_raw(raw)).into_color_unclamped()
+}
+
+/// Lightness, chroma and hue differences of `b` against the reference `a`,
+/// together with the chroma of `a`. The hue difference is squared
+#[inline(always)]
+fn lch_difference(a: &MyLab, b: &MyLab) -> (f32, f32, f32, f32) {
+    let c1 = a.a.hypot(a.b);
+    let c2 = b.a.hypot(b.b);
+    let dl = a.l - b.l;
+    let dc = c1 - c2;
+    let dh2 = ((a.a - b.a).powi(2) + (a.b - b.b).powi(2) - dc.powi(2)).max(0.);
+    (dl, dc, dh2, c1)
+}
+
+pub(crate) fn cie76<T: Copy + Pixel<f32> + IntoColorUnclamped<MyLab>>(
+    a: &RawColor,
+    b: &RawColor,
+) -> f32 {
+    let a = to_lab::<T>(a);
+    let b = to_lab::<T>(b);
+    ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+}
+
+/// `TEXTILES` selects the textiles application weights instead of the graphic arts ones
+pub(crate) fn cie94<T: Copy + Pixel<f32> + IntoColorUnclamped<MyLab>, const TEXTILES: bool>(
+    a: &RawColor,
+    b: &RawColor,
+) -> f32 {
+    let (kl, k1, k2) = match TEXTILES {
+        true => (2., 0.048, 0.014),
+        false => (1., 0.045, 0.015),
+    };
+    let (dl, dc, dh2, c1) = lch_difference(&to_lab::<T>(a), &to_lab::<T>(b));
+    let sc = 1. + k1 * c1;
+    let sh = 1. + k2 * c1;
+    ((dl / kl).powi(2) + (dc / sc).powi(2) + dh2 / sh.powi(2)).sqrt()
+}
+
+/// CMC l:c, usually 2:1 for acceptability and 1:1 for perceptibility
+pub(crate) fn cmc<T: Copy + Pixel<f32> + IntoColorUnclamped<MyLab>>(
+    a: &RawColor,
+    b: &RawColor,
+    l: f32,
+    c: f32,
+) -> f32 {
+    let a = to_lab::<T>(a);
+    let (dl, dc, dh2, c1) = lch_difference(&a, &to_lab::<T>(b));
+    let h1 = a.b.atan2(a.a).to_degrees().rem_euclid(360.);
+
+    let sl = match a.l < 16. {
+        true => 0.511,
+        false => 0.040_975 * a.l / (1. + 0.017_65 * a.l),
+    };
+    let sc = 0.063_8 * c1 / (1. + 0.013_1 * c1) + 0.638;
+    let f = (c1.powi(4) / (c1.powi(4) + 1900.)).sqrt();
+    let t = match (164. ..=345.).contains(&h1) {
+        true => 0.56 + (0.2 * (h1 + 168.).to_radians().cos()).abs(),
+        false => 0.36 + (0.4 * (h1 + 35.).to_radians().cos()).abs(),
+    };
+    let sh = sc * (f * t + 1. - f);
+
+    ((dl / (l * sl)).powi(2) + (dc / (c * sc)).powi(2) + dh2 / sh.powi(2)).sqrt()
+}
+
+pub(crate) fn ciede2000<T: Copy + Pixel<f32> + IntoColorUnclamped<MyLab>>(
+    a: &RawColor,
+    b: &RawColor,
+) -> f32 {
+    to_lab::<T>(a).get_color_difference(&to_lab::<T>(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MyLab;
+
+    // See [The CIEDE2000 Color-Difference Formula](http://www2.ece.rochester.edu/~gsharma/ciede2000/)
+    const A: [f32; 3] = [50., 2.6772, -79.7751];
+    const B: [f32; 3] = [50., 0., -82.7485];
+
+    #[test]
+    fn difference() {
+        let cases = [
+            (super::cie76::<MyLab>(&A, &B), 4.0011),
+            (super::cie94::<MyLab, false>(&A, &B), 1.3950),
+            (super::ciede2000::<MyLab>(&A, &B), 2.0425),
+        ];
+        for (value, expected) in cases {
+            assert!((value - expected).abs() < 1e-3, "{} != {}", value, expected);
+        }
+        assert_eq!(super::cmc::<MyLab>(&A, &A, 2., 1.), 0.);
+    }
+
+    // See [colour-science](https://github.com/colour-science/colour/blob/develop/colour/difference/tests/test_delta_e.py)
+    #[test]
+    fn cmc() {
+        const C: [f32; 3] = [100., 21.572_104, 272.228_2];
+        let cases = [
+            ([100., 426.679_45, 72.395_91], 2., 172.704_77),
+            ([100., 74.052_17, 276.453_2], 2., 20.597_306),
+            ([100., 8.322_82, -73.582_98], 2., 121.718_33),
+            ([100., 426.679_45, 72.395_91], 1., 172.704_77),
+        ];
+        for (other, l, expected) in cases {
+            let value = super::cmc::<MyLab>(&C, &other, l, 1.);
+            assert!((value - expected).abs() < 1e-2, "{} != {}", value, expected);
+        }
+    }
+}
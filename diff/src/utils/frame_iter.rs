@@ -1,35 +1,70 @@
-use image::RgbImage;
+use {
+    crate::{Error, ProcessConfig, Warning},
+    image::RgbImage,
+};
 
-pub trait FrameIter {
-    fn new(input: String, output: String) -> (Self, i64, u32, u32)
+/// Where a frame goes in the output: the index of its stream and its timestamp
+pub type Stamp = (usize, Option<i64>);
+
+/// Reads the frames of the input, while its writer writes them to the output.
+/// The two halves run on threads of their own
+pub trait FrameIter: Send {
+    type Writer: FrameWriter + Send + 'static;
+
+    fn new(
+        input: String,
+        output: String,
+        config: &ProcessConfig,
+    ) -> Result<(Self, Self::Writer, i64, u32, u32), Error>
     where
         Self: Sized;
 
-    fn next(&mut self) -> Option<RgbImage>;
+    fn next(&mut self) -> Result<Option<(RgbImage, Stamp)>, Error>;
+
+    /// What the output leaves out of the input
+    fn warnings(&self) -> &[Warning] {
+        &[]
+    }
+}
 
-    fn post_next(&mut self, img: &RgbImage);
+pub trait FrameWriter {
+    /// The frames come back in the order they were read
+    fn post_next(&mut self, img: &RgbImage, stamp: Stamp) -> Result<(), Error>;
 
-    fn flush(&mut self) {}
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 pub(crate) struct ImageDump {
     img: Option<RgbImage>,
+}
+
+pub(crate) struct ImageWriter {
     output: String,
 }
 
 impl FrameIter for ImageDump {
-    fn new(input: String, output: String) -> (Self, i64, u32, u32) {
-        let img = image::open(input).unwrap().into_rgb8();
+    type Writer = ImageWriter;
+
+    fn new(
+        input: String,
+        output: String,
+        _config: &ProcessConfig,
+    ) -> Result<(Self, Self::Writer, i64, u32, u32), Error> {
+        let img = image::open(input).map_err(Error::unsupported)?.into_rgb8();
         let (width, height) = img.dimensions();
         let img = Some(img);
-        (Self { img, output }, 1, width, height)
+        Ok((Self { img }, ImageWriter { output }, 1, width, height))
     }
 
-    fn next(&mut self) -> Option<RgbImage> {
-        self.img.take()
+    fn next(&mut self) -> Result<Option<(RgbImage, Stamp)>, Error> {
+        Ok(self.img.take().map(|img| (img, (0, None))))
     }
+}
 
-    fn post_next(&mut self, img: &RgbImage) {
-        let _ = img.save(&self.output);
+impl FrameWriter for ImageWriter {
+    fn post_next(&mut self, img: &RgbImage, _stamp: Stamp) -> Result<(), Error> {
+        img.save(&self.output).map_err(Error::write)
     }
 }
@@ -0,0 +1,86 @@
+use crate::RawColor;
+
+/// Named colors to crawl for, the names are those of the color filter of Pixabay
+pub const COLOR_BUCKETS: [(&str, [u8; 3]); 12] = [
+    ("red", [220, 30, 30]),
+    ("orange", [245, 140, 30]),
+    ("yellow", [245, 220, 40]),
+    ("green", [60, 160, 60]),
+    ("turquoise", [40, 200, 190]),
+    ("blue", [40, 80, 210]),
+    ("lilac", [180, 140, 220]),
+    ("pink", [245, 150, 190]),
+    ("white", [245, 245, 245]),
+    ("gray", [128, 128, 128]),
+    ("black", [15, 15, 15]),
+    ("brown", [120, 75, 40]),
+];
+
+/// Sums the distance of each target color to its nearest library item per bucket, as given
+/// by `gap`, the target colors are assigned to their nearest bucket. The scores add up to 1,
+/// or are all 0 if the library covers every color within `tolerance`
+pub(crate) fn gap_scores<T>(
+    colors: &[RawColor],
+    lib: &[T],
+    buckets: &[RawColor],
+    distance: impl Fn(&RawColor, &RawColor) -> f32,
+    gap: impl Fn(&RawColor, &T) -> f32,
+    tolerance: f32,
+) -> Vec<f32> {
+    let mut scores = vec![0.; buckets.len()];
+    for color in colors {
+        let gap = lib
+            .iter()
+            .map(|lib| gap(color, lib))
+            .fold(f32::INFINITY, f32::min);
+        if gap <= tolerance {
+            continue;
+        }
+        let nearest = buckets
+            .iter()
+            .map(|bucket| distance(color, bucket))
+            .enumerate()
+            .fold((0, f32::INFINITY), |min, (idx, d)| match d < min.1 {
+                true => (idx, d),
+                false => min,
+            })
+            .0;
+        // a library without any color leaves every color uncovered
+        scores[nearest] += match gap.is_finite() {
+            true => gap,
+            false => 1.,
+        };
+    }
+    let sum = scores.iter().sum::<f32>();
+    if sum > 0. {
+        scores.iter_mut().for_each(|score| *score /= sum);
+    }
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::gap_scores;
+
+    #[test]
+    fn gaps() {
+        let distance =
+            |a: &[f32; 3], b: &[f32; 3]| (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt();
+        let buckets = [[0.; 3], [1.; 3]];
+        let colors = [[0.; 3], [0.1; 3], [0.9; 3], [1.; 3]];
+
+        // the dark colors are covered
+        let scores = gap_scores(&colors, &[[0.05; 3]], &buckets, distance, distance, 0.1);
+        assert_eq!(scores[0], 0.);
+        assert_eq!(scores[1], 1.);
+
+        assert_eq!(
+            gap_scores(&colors, &[], &buckets, distance, distance, 0.1),
+            [0.5, 0.5]
+        );
+        assert_eq!(
+            gap_scores(&colors, &colors, &buckets, distance, distance, 0.1),
+            [0., 0.]
+        );
+    }
+}
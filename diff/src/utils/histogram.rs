@@ -0,0 +1,64 @@
+use image::RgbImage;
+
+const BINS: usize = 32;
+/// Every `STEP`-th pixel of every `STEP`-th row is counted
+const STEP: usize = 4;
+
+/// Color histogram of a frame, compared to detect scene changes
+pub(crate) struct Histogram {
+    bins: [[u32; BINS]; 3],
+    count: u32,
+}
+
+impl Histogram {
+    pub(crate) fn new(img: &RgbImage) -> Self {
+        let mut bins = [[0; BINS]; 3];
+        let mut count = 0;
+        for y in (0..img.height()).step_by(STEP) {
+            for x in (0..img.width()).step_by(STEP) {
+                let pixel = img.get_pixel(x, y);
+                for (channel, &value) in bins.iter_mut().zip(pixel.0.iter()) {
+                    channel[value as usize * BINS / 256] += 1;
+                }
+                count += 1;
+            }
+        }
+        Self { bins, count }
+    }
+
+    /// Half of the L1 distance of the normalized histograms averaged over the channels,
+    /// 0 for the same distribution and 1 for disjoint ones
+    pub(crate) fn difference(&self, other: &Self) -> f32 {
+        if self.count == 0 || other.count == 0 {
+            return 1.;
+        }
+        let (a, b) = (self.count as f32, other.count as f32);
+        let sum = self
+            .bins
+            .iter()
+            .flatten()
+            .zip(other.bins.iter().flatten())
+            .map(|(&x, &y)| (x as f32 / a - y as f32 / b).abs())
+            .sum::<f32>();
+        sum / 6.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::Histogram, image::RgbImage};
+
+    #[test]
+    fn difference() {
+        let black = Histogram::new(&RgbImage::new(16, 16));
+        let white = Histogram::new(&RgbImage::from_pixel(16, 16, [255; 3].into()));
+        let half = Histogram::new(&RgbImage::from_fn(16, 16, |x, _| match x < 8 {
+            true => [0; 3].into(),
+            false => [255; 3].into(),
+        }));
+
+        assert_eq!(black.difference(&black), 0.);
+        assert!((black.difference(&white) - 1.).abs() < 1e-6);
+        assert!((black.difference(&half) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,43 @@
+use super::RawColor;
+
+/// HSV as a cone, so that the hue wraps around and matters less for dark colors
+/// See [HSL and HSV](https://en.wikipedia.org/wiki/HSL_and_HSV#Basic_principle)
+#[inline(always)]
+pub(crate) fn hsv_to_cone(&[h, s, v]: &RawColor) -> RawColor {
+    let (sin, cos) = h.to_radians().sin_cos();
+    [s * v * cos, s * v * sin, v]
+}
+
+#[inline(always)]
+pub(crate) fn cone_to_hsv(&[x, y, v]: &RawColor) -> RawColor {
+    let s = match v > 0. {
+        true => (x.hypot(y) / v).min(1.),
+        false => 0.,
+    };
+    [y.atan2(x).to_degrees().rem_euclid(360.), s, v]
+}
+
+#[inline(always)]
+pub(crate) fn lch_to_lab(&[l, c, h]: &RawColor) -> RawColor {
+    let (sin, cos) = h.to_radians().sin_cos();
+    [l, c * cos, c * sin]
+}
+
+#[inline(always)]
+pub(crate) fn lab_to_lch(&[l, a, b]: &RawColor) -> RawColor {
+    [l, a.hypot(b), b.atan2(a).to_degrees().rem_euclid(360.)]
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn hue_wrap_around() {
+        // the average of red hues is still red
+        let a = super::hsv_to_cone(&[350., 1., 1.]);
+        let b = super::hsv_to_cone(&[10., 1., 1.]);
+        let mean = [(a[0] + b[0]) / 2., (a[1] + b[1]) / 2., (a[2] + b[2]) / 2.];
+        let [h, _, v] = super::cone_to_hsv(&mean);
+        assert!(h.min(360. - h) < 1e-3);
+        assert_eq!(v, 1.);
+    }
+}
@@ -0,0 +1,104 @@
+use {super::RawColor, crate::Mask};
+
+/// Summed-area tables of the channels and of their squares,
+/// the mean and the variance of any mask are computed in constant time
+/// See [Summed-area table](https://en.wikipedia.org/wiki/Summed-area_table)
+pub(crate) struct Integral {
+    stride: usize,
+    sum: Box<[[f64; 3]]>,
+    /// Only built for the variance
+    square: Option<Box<[[f64; 3]]>>,
+}
+
+impl Integral {
+    /// The squares are summed only if `variance` is needed
+    pub(crate) fn new(
+        width: u32,
+        height: u32,
+        variance: bool,
+        pixel: impl Fn(u32, u32) -> RawColor,
+    ) -> Self {
+        let stride = width as usize + 1;
+        let len = stride * (height as usize + 1);
+        let mut sum = vec![[0f64; 3]; len];
+        let mut square = match variance {
+            true => vec![[0f64; 3]; len],
+            false => Vec::new(),
+        };
+        for y in 0..height {
+            let mut row_sum = [0f64; 3];
+            let mut row_square = [0f64; 3];
+            for x in 0..width {
+                let raw = pixel(x, y);
+                let idx = (y as usize + 1) * stride + x as usize + 1;
+                for (c, value) in raw.into_iter().enumerate() {
+                    let value = value as f64;
+                    row_sum[c] += value;
+                    sum[idx][c] = sum[idx - stride][c] + row_sum[c];
+                    if variance {
+                        row_square[c] += value * value;
+                        square[idx][c] = square[idx - stride][c] + row_square[c];
+                    }
+                }
+            }
+        }
+        Self {
+            stride,
+            sum: sum.into_boxed_slice(),
+            square: variance.then(|| square.into_boxed_slice()),
+        }
+    }
+
+    #[inline(always)]
+    fn area(&self, table: &[[f64; 3]], (x, y, w, h): Mask) -> [f64; 3] {
+        let top_left = y as usize * self.stride + x as usize;
+        let top_right = top_left + w as usize;
+        let bottom_left = top_left + h as usize * self.stride;
+        let bottom_right = bottom_left + w as usize;
+        let mut ans = [0f64; 3];
+        for (c, ans) in ans.iter_mut().enumerate() {
+            *ans = table[bottom_right][c] - table[top_right][c] - table[bottom_left][c]
+                + table[top_left][c];
+        }
+        ans
+    }
+
+    #[inline(always)]
+    pub(crate) fn mean(&self, mask: Mask) -> RawColor {
+        let count = (mask.2 * mask.3) as f64;
+        self.area(&self.sum, mask).map(|sum| (sum / count) as f32)
+    }
+
+    /// Panics if the squares were not summed
+    #[inline(always)]
+    pub(crate) fn variance(&self, mask: Mask) -> RawColor {
+        let count = (mask.2 * mask.3) as f64;
+        let sum = self.area(&self.sum, mask);
+        let square = self.area(self.square.as_ref().expect("no squares summed"), mask);
+        let mut ans = [0f32; 3];
+        for (c, ans) in ans.iter_mut().enumerate() {
+            let mean = sum[c] / count;
+            *ans = (square[c] / count - mean * mean).max(0.) as f32;
+        }
+        ans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Integral;
+
+    #[test]
+    fn mean_and_variance() {
+        let pixel = |x, y| [(x + y * 4) as f32, 1., x as f32];
+        let integral = Integral::new(4, 3, true, pixel);
+
+        assert_eq!(integral.mean((0, 0, 4, 3)), [5.5, 1., 1.5]);
+        assert_eq!(integral.mean((1, 1, 2, 2)), [7.5, 1., 1.5]);
+        assert_eq!(integral.variance((1, 1, 2, 2)), [4.25, 0., 0.25]);
+
+        let means = Integral::new(4, 3, false, pixel);
+        assert!(means.square.is_none());
+        assert_eq!(means.mean((1, 1, 2, 2)), [7.5, 1., 1.5]);
+    }
+}
@@ -0,0 +1,261 @@
+use {
+    super::IMAGE_FILTER,
+    glob::{MatchOptions, Pattern},
+    rand::{rngs::StdRng, seq::SliceRandom, SeedableRng},
+    std::{
+        collections::HashSet,
+        fs::{read_dir, symlink_metadata},
+        io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// `*` and `?` stop at the path separators, `**` crosses them
+const MATCH: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// How the symbolic links under the library roots are treated
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Symlinks {
+    Skip,
+    /// The folders already scanned through another path are skipped, so loops end
+    Follow,
+}
+
+impl Default for Symlinks {
+    fn default() -> Self {
+        Self::Skip
+    }
+}
+
+/// Which images under the library roots are used
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryConfig {
+    /// The depth of the subfolders scanned, only the files of the roots if it is 0 and all of
+    /// them if there is none
+    pub max_depth: Option<usize>,
+    pub symlinks: Symlinks,
+    /// Glob patterns of the paths relative to the root, one of which the images must match.
+    /// A pattern without a `/` matches the file name. All the images match if there is none
+    pub include: Vec<String>,
+    /// Glob patterns of the images and the folders that are skipped, as `include`
+    pub exclude: Vec<String>,
+    /// Samples this many images at random if there are more
+    pub max_files: Option<usize>,
+    pub seed: u64,
+}
+
+struct Scan<'a> {
+    config: &'a LibraryConfig,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    /// The canonical paths of the scanned folders
+    visited: HashSet<PathBuf>,
+}
+
+impl Scan<'_> {
+    fn visit(&mut self, root: &Path, dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+        if let Ok(dir) = dir.canonicalize() {
+            if !self.visited.insert(dir) {
+                return;
+            }
+        }
+        let mut entries = match read_dir(dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>(),
+            // unreadable subfolders are skipped
+            Err(_) => return,
+        };
+        // the same files are sampled with the same seed
+        entries.sort();
+
+        for path in entries {
+            let link = symlink_metadata(&path)
+                .map(|meta| meta.file_type().is_symlink())
+                .unwrap_or(true);
+            if link && self.config.symlinks == Symlinks::Skip {
+                continue;
+            }
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            if matches(&self.exclude, relative) {
+                continue;
+            }
+            if path.is_dir() {
+                if !matches!(self.config.max_depth, Some(max) if depth >= max) {
+                    self.visit(root, &path, depth + 1, files);
+                }
+            } else if is_image(&path)
+                && (self.include.is_empty() || matches(&self.include, relative))
+            {
+                files.push(path);
+            }
+        }
+    }
+}
+
+fn matches(patterns: &[Pattern], path: &Path) -> bool {
+    patterns.iter().any(|pattern| {
+        if pattern.as_str().contains('/') {
+            pattern.matches_path_with(path, MATCH)
+        } else {
+            match path.file_name() {
+                Some(name) => pattern.matches_with(&name.to_string_lossy(), MATCH),
+                None => false,
+            }
+        }
+    })
+}
+
+/// By the extension, in any case
+fn is_image(path: &Path) -> bool {
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    IMAGE_FILTER
+        .iter()
+        .any(|filter| filter.eq_ignore_ascii_case(&ext))
+}
+
+/// Lists the images under the roots, a root may also be an image itself
+pub fn scan_library(roots: &[PathBuf], config: &LibraryConfig) -> io::Result<Vec<PathBuf>> {
+    let patterns = |globs: &[String]| {
+        globs
+            .iter()
+            .map(|glob| {
+                Pattern::new(glob).map_err(|err| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", glob, err))
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()
+    };
+    let mut scan = Scan {
+        config,
+        include: patterns(&config.include)?,
+        exclude: patterns(&config.exclude)?,
+        visited: HashSet::new(),
+    };
+
+    let mut files = Vec::new();
+    for root in roots {
+        if root.is_file() {
+            if is_image(root) {
+                files.push(root.clone());
+            }
+            continue;
+        }
+        // only a missing or unreadable root is an error
+        read_dir(root)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", root.display(), err)))?;
+        scan.visit(root, root, 0, &mut files);
+    }
+
+    match config.max_files {
+        Some(max) if files.len() > max => {
+            let mut rng = StdRng::seed_from_u64(config.seed);
+            let mut sample = files
+                .choose_multiple(&mut rng, max)
+                .cloned()
+                .collect::<Vec<_>>();
+            sample.sort();
+            Ok(sample)
+        }
+        _ => Ok(files),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{scan_library, LibraryConfig, Symlinks},
+        std::{
+            fs::{create_dir_all, remove_dir_all, write},
+            path::{Path, PathBuf},
+        },
+    };
+
+    fn names(root: &Path, files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .map(|file| {
+                let file = file.strip_prefix(root).unwrap_or(file);
+                file.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scan() {
+        let root = PathBuf::from("test-library");
+        let _ = remove_dir_all(&root);
+        for file in [
+            "a.jpg",
+            "notes.txt",
+            "2020/b.PNG",
+            "2020/thumbs/b.jpg",
+            "2020/trip/c.jpeg",
+            "2021/d.jpg",
+        ] {
+            let path = root.join(file);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, b"").unwrap();
+        }
+        #[cfg(unix)]
+        {
+            // a loop and a link to a folder scanned anyway
+            std::os::unix::fs::symlink("..", root.join("2021/up")).unwrap();
+            std::os::unix::fs::symlink("2020", root.join("link")).unwrap();
+        }
+        let roots = [root.clone()];
+        let scan = |config: &LibraryConfig| {
+            let files = scan_library(&roots, config).unwrap();
+            names(&root, &files)
+        };
+
+        let all = [
+            "2020/b.PNG",
+            "2020/thumbs/b.jpg",
+            "2020/trip/c.jpeg",
+            "2021/d.jpg",
+            "a.jpg",
+        ];
+        let files = scan(&LibraryConfig::default());
+        let max_depth = scan(&LibraryConfig {
+            max_depth: Some(1),
+            ..Default::default()
+        });
+        let globs = scan(&LibraryConfig {
+            include: vec!["2020/**/*".into(), "*.jpg".into()],
+            exclude: vec!["thumbs".into(), "2021".into()],
+            ..Default::default()
+        });
+        let follow = scan(&LibraryConfig {
+            symlinks: Symlinks::Follow,
+            ..Default::default()
+        });
+        let sample = |seed| {
+            scan(&LibraryConfig {
+                max_files: Some(2),
+                seed,
+                ..Default::default()
+            })
+        };
+        let samples = (sample(7), sample(7));
+        let missing = scan_library(&[root.join("missing")], &LibraryConfig::default());
+        let file = scan_library(&[root.join("a.jpg")], &LibraryConfig::default());
+        remove_dir_all(&root).unwrap();
+
+        assert_eq!(files, all);
+        assert_eq!(max_depth, ["2020/b.PNG", "2021/d.jpg", "a.jpg"]);
+        assert_eq!(globs, ["2020/b.PNG", "2020/trip/c.jpeg", "a.jpg"]);
+        // every folder once, whichever path reaches it first
+        assert_eq!(follow.len(), all.len());
+        assert_eq!(samples.0.len(), 2);
+        assert_eq!(samples.0, samples.1);
+        assert!(samples.0.iter().all(|file| all.contains(&file.as_str())));
+        assert!(missing.is_err());
+        assert_eq!(file.unwrap(), [root.join("a.jpg")]);
+    }
+}
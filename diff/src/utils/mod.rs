@@ -1,18 +1,47 @@
+mod audio;
+mod cluster;
+mod color_image;
+mod dedup;
+mod difference;
 mod frame_iter;
+mod gaps;
+mod histogram;
+mod hue;
+mod integral;
+mod library;
+mod quality;
 mod transcoder;
-mod variance;
+mod trim;
+mod ycbcr;
 
 use {
     kmeans_colors::{Calculate, Hamerly, Sort},
     palette::{
-        convert::FromColorUnclamped, encoding, white_point::D65, Clamp, ColorDifference, Hsv,
-        IntoColor, Lab, Pixel, Srgb,
+        convert::FromColorUnclamped, encoding, white_point::D65, Clamp, Hsv, IntoColor, Lab, Lch,
+        Oklab, Pixel, Srgb, Xyz,
     },
     std::cmp::{Ord, Ordering, PartialEq},
 };
 
 pub(crate) use {
-    frame_iter::FrameIter, frame_iter::ImageDump, transcoder::Transcode, variance::Variance,
+    cluster::Point,
+    color_image::ColorImage,
+    dedup::keep_unique,
+    difference::{cie76, cie94, ciede2000, cmc},
+    frame_iter::{FrameIter, FrameWriter, ImageDump, Stamp},
+    gaps::gap_scores,
+    histogram::Histogram,
+    hue::{cone_to_hsv, hsv_to_cone, lab_to_lch, lch_to_lab},
+    integral::Integral,
+    transcoder::Transcode,
+    trim::{Selection, Trim},
+    ycbcr::YCbCr,
+};
+
+pub use {
+    gaps::COLOR_BUCKETS,
+    library::{scan_library, LibraryConfig, Symlinks},
+    quality::QualityFilter,
 };
 
 pub const IMAGE_FILTER: [&str; 3] = ["png", "jpg", "jpeg"];
@@ -21,27 +50,72 @@ pub const VIDEO_FILTER: [&str; 1] = ["mp4"];
 pub(crate) type MySrgb = Srgb<f32>;
 pub(crate) type MyHsv = Hsv<encoding::Srgb, f32>;
 pub(crate) type MyLab = Lab<D65, f32>;
+pub(crate) type MyLch = Lch<D65, f32>;
+pub(crate) type MyOklab = Oklab<f32>;
+pub(crate) type MyXyz = Xyz<D65, f32>;
 pub type RawColor = [f32; 3];
-
-// TODO: How to calc distance between HSV color
+/// Dominant colors and their weights, sorted by weight in descending order
+pub type Palette = Box<[(RawColor, f32)]>;
 
 pub trait Color:
-    Copy
-    + Clone
-    + Hamerly
-    + Calculate
-    + Sort
-    + Clamp
-    + Pixel<f32>
-    + FromColorUnclamped<palette::rgb::Rgb>
-    + Sync
-    + Send
+    Copy + Clone + Clamp + Pixel<f32> + FromColorUnclamped<palette::rgb::Rgb> + Sync + Send
 {
 }
 
 impl Color for MySrgb {}
 impl Color for MyHsv {}
 impl Color for MyLab {}
+impl Color for MyLch {}
+impl Color for MyOklab {}
+impl Color for MyXyz {}
+impl Color for YCbCr {}
+
+/// Color spaces in which k-means clusters
+pub trait Cluster: Color {
+    /// What k-means runs on, the color itself if kmeans_colors supports its space
+    type Point: Calculate + Hamerly + Sort + Clone;
+
+    fn into_point(self) -> Self::Point;
+
+    fn from_point(point: Self::Point) -> Self;
+}
+
+macro_rules! impl_cluster {
+    ($($color:ty),*; $($coords:ty),*) => {
+        $(
+            impl Cluster for $color {
+                type Point = Self;
+
+                #[inline(always)]
+                fn into_point(self) -> Self::Point {
+                    self
+                }
+
+                #[inline(always)]
+                fn from_point(point: Self::Point) -> Self {
+                    point
+                }
+            }
+        )*
+        $(
+            impl Cluster for $coords {
+                type Point = Point<Self>;
+
+                #[inline(always)]
+                fn into_point(self) -> Self::Point {
+                    Point::new(self)
+                }
+
+                #[inline(always)]
+                fn from_point(point: Self::Point) -> Self {
+                    point.color()
+                }
+            }
+        )*
+    };
+}
+
+impl_cluster!(MySrgb, MyHsv, MyLab; MyLch, MyOklab, MyXyz, YCbCr);
 
 #[inline(always)]
 pub(crate) fn converter<T: Color>(rgb: &[u8]) -> RawColor {
@@ -49,15 +123,6 @@ pub(crate) fn converter<T: Color>(rgb: &[u8]) -> RawColor {
     color.into_raw()
 }
 
-pub(crate) fn ciede2000<T: Copy + Pixel<f32> + IntoColor<MyLab>>(
-    a: &RawColor,
-    b: &RawColor,
-) -> f32 {
-    let a: MyLab = (*T::from_raw(a)).into_color();
-    let b: MyLab = (*T::from_raw(b)).into_color();
-    a.get_color_difference(&b)
-}
-
 pub(crate) struct F32Wrapper(pub f32);
 
 impl PartialEq for F32Wrapper {
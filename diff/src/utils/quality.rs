@@ -0,0 +1,156 @@
+use {
+    image::{DynamicImage, GenericImageView, GrayImage},
+    std::collections::HashMap,
+};
+
+/// The size the images are reduced to before they are measured, so that the measures do not
+/// depend on the resolution
+const THUMBNAIL: u32 = 128;
+
+/// Filters dropping the library images that make poor tiles, each one is off if it is none
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QualityFilter {
+    /// The minimum variance of the Laplacian of the luma, blurry images have little
+    pub min_sharpness: Option<f32>,
+    /// The minimum standard deviation of the luma, nearly uniform images have little
+    pub min_contrast: Option<f32>,
+    /// The maximum share of the pixels in the four most frequent colors, text and screenshots
+    /// are drawn with a few flat colors
+    pub max_flat_share: Option<f32>,
+    /// The maximum ratio of the longer side to the shorter one, banners crop badly
+    pub max_aspect_ratio: Option<f32>,
+}
+
+impl QualityFilter {
+    pub(crate) fn passes(&self, img: &DynamicImage) -> bool {
+        let (width, height) = img.dimensions();
+        if let Some(max) = self.max_aspect_ratio {
+            if width.max(height) as f32 > max * width.min(height).max(1) as f32 {
+                return false;
+            }
+        }
+        if self.min_sharpness.is_none()
+            && self.min_contrast.is_none()
+            && self.max_flat_share.is_none()
+        {
+            return true;
+        }
+
+        let thumb = img.thumbnail(THUMBNAIL, THUMBNAIL);
+        let luma = thumb.to_luma8();
+        if matches!(self.min_contrast, Some(min) if contrast(&luma) < min) {
+            return false;
+        }
+        if matches!(self.min_sharpness, Some(min) if sharpness(&luma) < min) {
+            return false;
+        }
+        !matches!(self.max_flat_share, Some(max) if flat_share(&thumb) > max)
+    }
+}
+
+/// The standard deviation of the luma
+fn contrast(luma: &GrayImage) -> f32 {
+    mean_var(luma.pixels().map(|pixel| pixel.0[0] as f32))
+        .1
+        .sqrt()
+}
+
+/// The variance of the 4-neighbor Laplacian of the luma
+fn sharpness(luma: &GrayImage) -> f32 {
+    let (width, height) = luma.dimensions();
+    if width < 3 || height < 3 {
+        return 0.;
+    }
+    let at = |x: u32, y: u32| luma.get_pixel(x, y).0[0] as f32;
+    let laplacian = (1..height - 1).flat_map(|y| {
+        (1..width - 1)
+            .map(move |x| at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4. * at(x, y))
+    });
+    mean_var(laplacian).1
+}
+
+/// The share of the pixels in the four most frequent colors, with 4 bits per channel
+fn flat_share(img: &DynamicImage) -> f32 {
+    let rgb = img.to_rgb8();
+    let mut counts = HashMap::new();
+    for pixel in rgb.pixels() {
+        let [r, g, b] = pixel.0.map(|value| value >> 4);
+        *counts.entry((r, g, b)).or_insert(0usize) += 1;
+    }
+    let mut counts = counts.into_values().collect::<Vec<_>>();
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    let top = counts.iter().take(4).sum::<usize>();
+    top as f32 / (rgb.width() * rgb.height()).max(1) as f32
+}
+
+fn mean_var(values: impl Iterator<Item = f32>) -> (f32, f32) {
+    let (mut count, mut sum, mut sum_sq) = (0., 0., 0.);
+    for value in values {
+        count += 1.;
+        sum += value;
+        sum_sq += value * value;
+    }
+    if count == 0. {
+        return (0., 0.);
+    }
+    let mean = sum / count;
+    (mean, (sum_sq / count - mean * mean).max(0.))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::QualityFilter,
+        image::{DynamicImage, RgbImage},
+    };
+
+    fn image(width: u32, height: u32, f: impl Fn(u32, u32) -> [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| f(x, y).into()))
+    }
+
+    #[test]
+    fn filters() {
+        // noise-like detail in every color
+        let photo = image(200, 150, |x, y| {
+            let v = (x * 7919 + y * 104_729) % 251;
+            [v as u8, (v * 3 % 251) as u8, (v * 7 % 251) as u8]
+        });
+        let smooth = image(200, 150, |x, _| [x as u8; 3]);
+        let flat = image(200, 150, |_, _| [120, 130, 140]);
+        let text = image(200, 150, |x, y| match (x / 3 + y / 5) % 4 == 0 {
+            true => [0; 3],
+            false => [255; 3],
+        });
+        let banner = image(800, 100, |x, y| [x as u8, y as u8, 0]);
+
+        let filter = QualityFilter {
+            min_sharpness: Some(50.),
+            ..Default::default()
+        };
+        assert!(filter.passes(&photo));
+        assert!(!filter.passes(&smooth));
+
+        let filter = QualityFilter {
+            min_contrast: Some(10.),
+            ..Default::default()
+        };
+        assert!(filter.passes(&smooth));
+        assert!(!filter.passes(&flat));
+
+        let filter = QualityFilter {
+            max_flat_share: Some(0.8),
+            ..Default::default()
+        };
+        assert!(filter.passes(&photo));
+        assert!(!filter.passes(&text));
+
+        let filter = QualityFilter {
+            max_aspect_ratio: Some(3.),
+            ..Default::default()
+        };
+        assert!(filter.passes(&photo));
+        assert!(!filter.passes(&banner));
+
+        assert!(QualityFilter::default().passes(&flat));
+    }
+}
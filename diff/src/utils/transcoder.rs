@@ -1,116 +1,235 @@
 use {
-    super::FrameIter,
+    super::{
+        audio::{copy_packet, copy_stream, supports, AudioTranscoder, ExternalAudio},
+        FrameIter, FrameWriter, Stamp,
+    },
+    crate::{Audio, Error, ProcessConfig, Selection, Trim, Warning},
     ffmpeg::{
-        codec, decoder, encoder, format, frame, media, picture, software, Dictionary, Packet,
-        Rational,
+        codec, decoder, encoder, ffi, format, frame, media, picture, rescale, software, Dictionary,
+        Packet, Rational, Rescale,
     },
     image::RgbImage,
-    std::collections::HashMap,
+    std::{
+        collections::HashMap,
+        os::raw::c_int,
+        ptr::{null, null_mut},
+        sync::mpsc::{channel, Receiver, Sender},
+    },
 };
 
+/// Reads the input: decodes the video streams and passes the packets of the other kept
+/// streams on to the writer
 pub(crate) struct Transcode {
     ictx: format::context::Input,
+    stream_mapping: Vec<isize>,
+    ist_time_bases: Vec<Rational>,
+    decoders: HashMap<usize, VideoDecoder>,
+    trim: Trim,
+    packets: Sender<(usize, Packet)>,
+    warnings: Vec<Warning>,
+
+    /// The stream whose decoder may still have frames
+    last: Option<usize>,
+}
+
+/// Writes the output: encodes the filled frames and writes the packets of the other streams
+pub(crate) struct TranscodeWriter {
     octx: format::context::Output,
     stream_mapping: Vec<isize>,
     ist_time_bases: Vec<Rational>,
     ost_time_bases: Vec<Rational>,
-    transcoders: HashMap<usize, Transcoder>,
-
-    last: Option<(usize, Rational, Option<i64>, frame::Video)>,
+    encoders: HashMap<usize, VideoEncoder>,
+    audio_transcoders: HashMap<usize, AudioTranscoder>,
+    external: Option<ExternalAudio>,
+    packets: Receiver<(usize, Packet)>,
+    /// The start of the rendered section in seconds of the input
+    start: f64,
+    /// The end of the frames written so far, in seconds of the output
+    duration: f64,
 }
 
-unsafe impl Sync for Transcode {}
+/// Used when the stream tells no frame rate, unless the fallback is disabled
+const FALLBACK_FRAME_RATE: Rational = Rational(25, 1);
+
+// SAFETY: the ffmpeg contexts are not bound to the thread that opened them. Each half is
+// owned by a single thread, the reader only uses the input and its decoders and the writer
+// only the output and its encoders
+unsafe impl Send for Transcode {}
+unsafe impl Send for TranscodeWriter {}
 
 impl FrameIter for Transcode {
-    fn new(input: String, output: String) -> (Self, i64, u32, u32) {
-        let ictx = format::input(&input).unwrap();
-        let mut octx = format::output(&output).unwrap();
+    type Writer = TranscodeWriter;
+
+    fn new(
+        input: String,
+        output: String,
+        config: &ProcessConfig,
+    ) -> Result<(Self, Self::Writer, i64, u32, u32), Error> {
+        let flags = config.scaling.into();
+        let fallback = config.fallback_frame_rate.then(|| FALLBACK_FRAME_RATE);
+        let trim = Trim::new(config);
+        let mut ictx = format::input(&input).map_err(Error::unsupported)?;
+        let mut octx = format::output(&output).map_err(Error::write)?;
         format::context::input::dump(&ictx, 0, Some(&input));
+        if trim.start() > 0. {
+            // to the key frame before the start, the frames in between are skipped
+            let timestamp = (trim.start() / f64::from(rescale::TIME_BASE)) as i64;
+            ictx.seek(timestamp, ..timestamp)
+                .map_err(Error::unsupported)?;
+        }
 
         let mut stream_mapping: Vec<isize> = vec![0; ictx.nb_streams() as _];
         let mut ist_time_bases = vec![Rational(0, 0); ictx.nb_streams() as _];
-        let mut ost_time_bases = vec![Rational(0, 0); ictx.nb_streams() as _];
-        let mut transcoders = HashMap::new();
+        let mut decoders = HashMap::new();
+        let mut encoders = HashMap::new();
+        let mut audio_transcoders = HashMap::new();
         let mut ost_index = 0;
+        let mut warnings = Vec::new();
+
+        let codec = match config.audio {
+            Audio::Keep | Audio::Drop => None,
+            Audio::Aac => Some(codec::Id::AAC),
+            Audio::Opus => Some(codec::Id::OPUS),
+        };
+        // an external audio replaces the audio streams of the input
+        let audio_file = config
+            .audio_file
+            .as_deref()
+            .filter(|_| config.audio != Audio::Drop);
 
         let mut cnt = 0;
         for (ist_index, ist) in ictx.streams().enumerate() {
             let ist_medium = ist.codec().medium();
-            if ist_medium != media::Type::Audio
-                && ist_medium != media::Type::Video
-                && ist_medium != media::Type::Subtitle
-            {
+            let keep = match ist_medium {
+                media::Type::Video => true,
+                media::Type::Audio => config.audio != Audio::Drop && audio_file.is_none(),
+                media::Type::Subtitle if config.subtitles => {
+                    // MP4 only holds text subtitles as mov_text, for example
+                    let id = ist.codec().id();
+                    let supported = supports(&octx, id);
+                    if !supported {
+                        warnings.push(Warning::DroppedSubtitles(ist_index, format!("{:?}", id)));
+                    }
+                    supported
+                }
+                _ => false,
+            };
+            if !keep {
                 stream_mapping[ist_index] = -1;
                 continue;
             }
             stream_mapping[ist_index] = ost_index;
             ist_time_bases[ist_index] = ist.time_base();
             if ist_medium == media::Type::Video {
-                cnt += ist.frames();
-                transcoders.insert(
-                    ist_index,
-                    Transcoder::new(&ist, &mut octx, ost_index as _).unwrap(),
-                );
+                let (decoder, encoder) = video_transcoder(
+                    &ist,
+                    &mut octx,
+                    ost_index as _,
+                    flags,
+                    fallback,
+                    trim.start(),
+                )?;
+                let frames = frame_count(&ist, ictx.duration(), decoder.frame_rate);
+                cnt += trim.frames(frames, f64::from(decoder.frame_rate));
+                decoders.insert(ist_index, decoder);
+                encoders.insert(ist_index, encoder);
+            } else if let (media::Type::Audio, Some(id)) = (ist_medium, codec) {
+                let transcoder = AudioTranscoder::new(&ist, &mut octx, ost_index as _, id)?;
+                audio_transcoders.insert(ist_index, transcoder);
             } else {
-                let mut ost = octx.add_stream(encoder::find(codec::Id::None)).unwrap();
-                ost.set_parameters(ist.parameters());
-                unsafe {
-                    (*ost.parameters().as_mut_ptr()).codec_tag = 0;
-                }
+                copy_stream(&ist, &mut octx)?;
             }
             ost_index += 1;
         }
 
+        let external = match audio_file {
+            Some(path) => Some(ExternalAudio::new(path, &mut octx, ost_index as _, codec)?),
+            None => None,
+        };
+
+        let (width, height) = match decoders.values().next() {
+            Some(decoder) => (decoder.width(), decoder.height()),
+            None => return Err(Error::MissingVideoStream),
+        };
+
         octx.set_metadata(ictx.metadata().to_owned());
         format::context::output::dump(&octx, 0, Some(&output));
-        octx.write_header().unwrap();
+        octx.write_header().map_err(Error::write)?;
 
-        for (ost_index, _) in octx.streams().enumerate() {
-            ost_time_bases[ost_index] = octx.stream(ost_index as _).unwrap().time_base();
-        }
+        let ost_time_bases = octx.streams().map(|ost| ost.time_base()).collect();
+        let (sender, packets) = channel();
+        let start = trim.start();
 
-        let rnd_frame = transcoders.values().next().unwrap();
-        let width = rnd_frame.width();
-        let height = rnd_frame.height();
-        (
+        Ok((
             Self {
                 ictx,
+                stream_mapping: stream_mapping.clone(),
+                ist_time_bases: ist_time_bases.clone(),
+                decoders,
+                trim,
+                packets: sender,
+                warnings,
+
+                last: None,
+            },
+            TranscodeWriter {
                 octx,
                 stream_mapping,
                 ist_time_bases,
                 ost_time_bases,
-                transcoders,
-
-                last: None,
+                encoders,
+                audio_transcoders,
+                external,
+                packets,
+                start,
+                duration: 0.,
             },
             cnt,
             width,
             height,
-        )
+        ))
     }
 
-    fn next(&mut self) -> Option<RgbImage> {
+    fn next(&mut self) -> Result<Option<(RgbImage, Stamp)>, Error> {
+        if self.trim.stopped() {
+            return Ok(None);
+        }
+        while let Some((ist_index, timestamp, img)) = self.decode()? {
+            let time_base = self.decoders[&ist_index].time_base;
+            let time = timestamp.map(|timestamp| timestamp as f64 * f64::from(time_base));
+            match self.trim.select(time) {
+                Selection::Skip => continue,
+                Selection::Stop => break,
+                Selection::Take => return Ok(Some((img, (ist_index, timestamp)))),
+            }
+        }
+        Ok(None)
+    }
+
+    fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+}
+
+impl Transcode {
+    /// The next decoded frame of any video stream, the decoders are drained at the end of the
+    /// input. The packets of the other streams within the range of the video go to the writer
+    fn decode(&mut self) -> Result<Option<(usize, Option<i64>, RgbImage)>, Error> {
         let Self {
             ictx,
-            octx,
             stream_mapping,
             ist_time_bases,
-            ost_time_bases,
-            transcoders,
+            decoders,
+            trim,
+            packets,
             last,
+            ..
         } = self;
 
-        if let Some((ist_index, _, old_timestamp, old_frame)) = last {
-            if let Some(transcoder) = transcoders.get_mut(ist_index) {
-                // transcoder.receive_and_process_decoded_frames(octx, ost_time_base);
-                if let Some((timestamp, frame)) = transcoder.receive_decoded_frames() {
-                    let width = transcoder.width();
-                    let height = transcoder.height();
-                    let buf = frame.data(0).to_owned();
-                    *old_timestamp = timestamp;
-                    *old_frame = frame;
-                    let img = RgbImage::from_raw(width, height, buf).unwrap();
-                    return Some(img);
+        if let Some(ist_index) = *last {
+            if let Some(decoder) = decoders.get_mut(&ist_index) {
+                if let Some((timestamp, img)) = decoder.receive_decoded_frames() {
+                    return Ok(Some((ist_index, timestamp, img)));
                 }
             }
         }
@@ -118,121 +237,276 @@ impl FrameIter for Transcode {
 
         for (stream, mut packet) in ictx.packets() {
             let ist_index = stream.index();
-            let ost_index = stream_mapping[ist_index];
-            if ost_index < 0 {
+            if stream_mapping[ist_index] < 0 {
                 continue;
             }
-            let ost_time_base = ost_time_bases[ost_index as usize];
-            match transcoders.get_mut(&ist_index) {
-                Some(transcoder) => {
-                    packet.rescale_ts(stream.time_base(), transcoder.decoder.time_base());
-                    transcoder.send_packet_to_decoder(&packet);
-                    // transcoder.receive_and_process_decoded_frames(octx, ost_time_base);
-                    if let Some((timestamp, frame)) = transcoder.receive_decoded_frames() {
-                        let width = transcoder.width();
-                        let height = transcoder.height();
-                        let buf = frame.data(0).to_owned();
-                        *last = Some((ist_index, ost_time_base, timestamp, frame));
-                        let img = RgbImage::from_raw(width, height, buf).unwrap();
-                        return Some(img);
+            match decoders.get_mut(&ist_index) {
+                Some(decoder) => {
+                    // a corrupt packet is skipped, the decoder recovers at the next key frame
+                    if decoder.send_packet_to_decoder(&packet).is_err() {
+                        continue;
+                    }
+                    if let Some((timestamp, img)) = decoder.receive_decoded_frames() {
+                        *last = Some(ist_index);
+                        return Ok(Some((ist_index, timestamp, img)));
                     }
                 }
                 None => {
-                    packet.rescale_ts(ist_time_bases[ist_index], ost_time_base);
-                    packet.set_position(-1);
-                    packet.set_stream(ost_index as _);
-                    packet.write_interleaved(octx).unwrap();
+                    let ist_time_base = ist_time_bases[ist_index];
+                    if let Some(pts) = packet.pts() {
+                        if !trim.contains(pts as f64 * f64::from(ist_time_base)) {
+                            continue;
+                        }
+                    }
+                    let start = (trim.start() / f64::from(ist_time_base)).round() as i64;
+                    packet.set_pts(packet.pts().map(|pts| pts - start));
+                    packet.set_dts(packet.dts().map(|dts| dts - start));
+                    // the writer hangs up after a failure, which it reports
+                    let _ = packets.send((ist_index, packet));
                 }
             }
         }
 
-        None
+        // the decoders still hold the frames delayed by reordering
+        for (&ist_index, decoder) in decoders.iter_mut() {
+            decoder.send_eof_to_decoder();
+            if let Some((timestamp, img)) = decoder.receive_decoded_frames() {
+                *last = Some(ist_index);
+                return Ok(Some((ist_index, timestamp, img)));
+            }
+        }
+
+        Ok(None)
     }
+}
 
-    fn post_next(&mut self, img: &RgbImage) {
+impl FrameWriter for TranscodeWriter {
+    fn post_next(&mut self, img: &RgbImage, (ist_index, timestamp): Stamp) -> Result<(), Error> {
+        self.write_packets()?;
         let Self {
             octx,
-            transcoders,
-            last,
+            ost_time_bases,
+            encoders,
+            external,
+            start,
+            duration,
             ..
         } = self;
-        if let Some((ist_index, ost_time_base, timestamp, frame)) = last {
-            if let Some(transcoder) = transcoders.get_mut(ist_index) {
-                // transcoder.receive_and_process_decoded_frames(octx, ost_time_base);
-                frame.data_mut(0).copy_from_slice(img.as_raw());
-                transcoder.process_decoded_frames(*timestamp, frame, octx, *ost_time_base);
+        if let Some(encoder) = encoders.get_mut(&ist_index) {
+            if let Some(timestamp) = timestamp {
+                let time = timestamp as f64 * f64::from(encoder.time_base);
+                let end = time - *start + f64::from(encoder.frame_rate.invert());
+                *duration = duration.max(end);
+            }
+            if let Some(external) = external {
+                let ost_time_base = ost_time_bases[external.ost_index];
+                external.write_until(*duration, octx, ost_time_base)?;
             }
+            let ost_time_base = ost_time_bases[encoder.ost_index];
+            encoder.process_decoded_frames(timestamp, img, octx, ost_time_base)?;
         }
+        Ok(())
     }
 
-    fn flush(&mut self) {
+    fn flush(&mut self) -> Result<(), Error> {
+        self.write_packets()?;
         let Self {
             octx,
+            stream_mapping,
             ost_time_bases,
-            transcoders,
+            encoders,
+            audio_transcoders,
+            external,
+            duration,
             ..
         } = self;
 
-        for (ost_index, transcoder) in transcoders.iter_mut() {
-            let ost_time_base = ost_time_bases[*ost_index];
-            transcoder.send_eof_to_decoder();
-            transcoder.receive_and_process_decoded_frames(octx, ost_time_base);
-            transcoder.send_eof_to_encoder();
-            transcoder.receive_and_process_encoded_packets(octx, ost_time_base);
+        for encoder in encoders.values_mut() {
+            let ost_time_base = ost_time_bases[encoder.ost_index];
+            encoder.send_eof_to_encoder()?;
+            encoder.receive_and_process_encoded_packets(octx, ost_time_base)?;
+        }
+        for (ist_index, transcoder) in audio_transcoders.iter_mut() {
+            let ost_time_base = ost_time_bases[stream_mapping[*ist_index] as usize];
+            transcoder.flush(octx, ost_time_base)?;
+        }
+        if let Some(external) = external {
+            let ost_time_base = ost_time_bases[external.ost_index];
+            external.write_until(*duration, octx, ost_time_base)?;
+            external.flush(octx, ost_time_base)?;
         }
 
-        octx.write_trailer().unwrap();
-
-        // to be dropped automatic
-        // self.stream_mapping.clear();
-        // self.stream_mapping.shrink_to_fit();
-        // self.ist_time_bases.clear();
-        // self.ist_time_bases.shrink_to_fit();
-        // self.ost_time_bases.clear();
-        // self.ost_time_bases.shrink_to_fit();
-        // self.transcoders.clear();
-        // self.transcoders.shrink_to_fit();
+        octx.write_trailer().map_err(Error::write)
     }
 }
 
-struct Transcoder {
-    ost_index: usize,
-    pub(super) decoder: decoder::Video,
-    encoder: encoder::video::Video,
+impl TranscodeWriter {
+    /// Writes the packets of the other streams the reader has passed on so far
+    fn write_packets(&mut self) -> Result<(), Error> {
+        let Self {
+            octx,
+            stream_mapping,
+            ist_time_bases,
+            ost_time_bases,
+            audio_transcoders,
+            packets,
+            ..
+        } = self;
+        for (ist_index, packet) in packets.try_iter() {
+            let ost_index = stream_mapping[ist_index] as usize;
+            let ost_time_base = ost_time_bases[ost_index];
+            match audio_transcoders.get_mut(&ist_index) {
+                Some(transcoder) => transcoder.send_packet(&packet, octx, ost_time_base)?,
+                None => copy_packet(
+                    packet,
+                    ist_time_bases[ist_index],
+                    ost_time_base,
+                    ost_index,
+                    octx,
+                )?,
+            }
+        }
+        Ok(())
+    }
 }
 
-impl Transcoder {
-    fn new(
-        ist: &format::stream::Stream,
-        octx: &mut format::context::Output,
-        ost_index: usize,
-    ) -> Result<Self, ffmpeg::Error> {
-        let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
-        let decoder = ist.codec().decoder().video()?;
-        let mut ost = octx.add_stream(encoder::find(codec::Id::H264))?;
-        let mut encoder = ost.codec().encoder().video()?;
-        encoder.set_height(decoder.height());
-        encoder.set_width(decoder.width());
-        encoder.set_aspect_ratio(decoder.aspect_ratio());
-        encoder.set_format(decoder.format());
-        encoder.set_frame_rate(decoder.frame_rate());
-        encoder.set_time_base(decoder.frame_rate().unwrap().invert());
-        if global_header {
-            encoder.set_flags(codec::Flags::GLOBAL_HEADER);
-        }
-        let mut x264_opts = Dictionary::new();
-        x264_opts.set("preset", "medium");
-        encoder.open_with(x264_opts)?;
-        encoder = ost.codec().encoder().video()?;
-        ost.set_parameters(encoder);
-        let encoder = ost.codec().encoder().video()?;
-        Ok(Self {
-            ost_index,
+/// Many containers do not store the number of frames, so it is estimated from the duration
+fn frame_count(ist: &format::stream::Stream, duration: i64, frame_rate: Rational) -> i64 {
+    match ist.frames() {
+        frames if frames > 0 => frames,
+        _ => estimate_frames(ist.duration(), ist.time_base(), duration, frame_rate),
+    }
+}
+
+/// From the duration of the stream in its time base,
+/// or else from the duration of the container in `AV_TIME_BASE` units
+fn estimate_frames(
+    stream_duration: i64,
+    time_base: Rational,
+    duration: i64,
+    frame_rate: Rational,
+) -> i64 {
+    let seconds = if stream_duration > 0 && valid(time_base).is_some() {
+        stream_duration as f64 * f64::from(time_base)
+    } else if duration > 0 {
+        duration as f64 * f64::from(rescale::TIME_BASE)
+    } else {
+        return 0;
+    };
+    (seconds * f64::from(frame_rate)).round() as i64
+}
+
+/// Keeps the timestamps increasing, even if some are missing or out of order
+#[inline(always)]
+fn increasing_pts(last: Option<i64>, timestamp: Option<i64>, frame_duration: i64) -> i64 {
+    match (timestamp, last) {
+        (Some(timestamp), Some(last)) if timestamp > last => timestamp,
+        (Some(timestamp), None) => timestamp,
+        (_, Some(last)) => last + frame_duration,
+        (None, None) => 0,
+    }
+}
+
+/// A rational with a zero or negative term is unset
+#[inline(always)]
+fn valid(r: Rational) -> Option<Rational> {
+    (r.numerator() > 0 && r.denominator() > 0).then(|| r)
+}
+
+/// The frames keep the time base of the input stream and their own timestamps,
+/// so that variable frame rate videos are not retimed
+fn video_transcoder(
+    ist: &format::stream::Stream,
+    octx: &mut format::context::Output,
+    ost_index: usize,
+    flags: software::scaling::Flags,
+    fallback: Option<Rational>,
+    start: f64,
+) -> Result<(VideoDecoder, VideoEncoder), Error> {
+    let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+    let decoder = ist.codec().decoder().video().map_err(Error::unsupported)?;
+    let h264 =
+        encoder::find(codec::Id::H264).ok_or_else(|| Error::EncoderUnavailable("H.264".into()))?;
+    let mut ost = octx.add_stream(h264).map_err(Error::encoder)?;
+    let mut encoder = ost.codec().encoder().video().map_err(Error::encoder)?;
+    encoder.set_height(decoder.height());
+    encoder.set_width(decoder.width());
+    encoder.set_aspect_ratio(decoder.aspect_ratio());
+    encoder.set_format(decoder.format());
+    let frame_rate = decoder
+        .frame_rate()
+        .and_then(valid)
+        .or_else(|| valid(ist.avg_frame_rate()))
+        .or_else(|| valid(ist.rate()))
+        .or(fallback)
+        .ok_or(Error::MissingFrameRate)?;
+    let time_base = valid(ist.time_base()).unwrap_or_else(|| frame_rate.invert());
+    encoder.set_frame_rate(Some(frame_rate));
+    encoder.set_time_base(time_base);
+    ost.set_time_base(time_base);
+    if global_header {
+        encoder.set_flags(codec::Flags::GLOBAL_HEADER);
+    }
+    let mut x264_opts = Dictionary::new();
+    x264_opts.set("preset", "medium");
+    encoder.open_with(x264_opts).map_err(Error::encoder)?;
+    encoder = ost.codec().encoder().video().map_err(Error::encoder)?;
+    ost.set_parameters(encoder);
+    let encoder = ost.codec().encoder().video().map_err(Error::encoder)?;
+    let (format, width, height) = (decoder.format(), decoder.width(), decoder.height());
+    let to_rgb = software::scaling::Context::get(
+        format,
+        width,
+        height,
+        format::Pixel::RGB24,
+        width,
+        height,
+        flags,
+    )
+    .map_err(Error::unsupported)?;
+    let from_rgb = software::scaling::Context::get(
+        format::Pixel::RGB24,
+        width,
+        height,
+        format,
+        width,
+        height,
+        flags,
+    )
+    .map_err(Error::unsupported)?;
+    Ok((
+        VideoDecoder {
             decoder,
+            to_rgb,
+            frame_rate,
+            time_base,
+        },
+        VideoEncoder {
+            ost_index,
             encoder,
-        })
-    }
+            from_rgb,
+            format,
+            width,
+            height,
+            frame_rate,
+            time_base,
+            frame_duration: 1i64.rescale(frame_rate.invert(), time_base).max(1),
+            start: (start / f64::from(time_base)).round() as i64,
+            last_pts: None,
+        },
+    ))
+}
 
+struct VideoDecoder {
+    decoder: decoder::Video,
+    /// Kept for the lifetime of the stream, from the decoded format to RGB
+    to_rgb: software::scaling::Context,
+    /// The average frame rate, to estimate the durations that are not stored
+    frame_rate: Rational,
+    time_base: Rational,
+}
+
+impl VideoDecoder {
     #[inline(always)]
     fn width(&self) -> u32 {
         self.decoder.width()
@@ -244,91 +518,176 @@ impl Transcoder {
     }
 
     #[inline(always)]
-    fn send_packet_to_decoder(&mut self, packet: &Packet) {
-        self.decoder.send_packet(packet).unwrap();
+    fn send_packet_to_decoder(&mut self, packet: &Packet) -> Result<(), Error> {
+        self.decoder.send_packet(packet).map_err(Error::unsupported)
     }
 
+    /// The decoder may be already drained
     #[inline(always)]
     fn send_eof_to_decoder(&mut self) {
-        self.decoder.send_eof().unwrap();
+        let _ = self.decoder.send_eof();
     }
 
-    fn receive_decoded_frames(&mut self) -> Option<(Option<i64>, frame::Video)> {
+    /// Scales the decoded frame straight into the buffer of the image
+    fn receive_decoded_frames(&mut self) -> Option<(Option<i64>, RgbImage)> {
         let mut decoded = frame::Video::empty();
         if self.decoder.receive_frame(&mut decoded).is_ok() {
-            let mut rgb_frame = frame::Video::empty();
             let timestamp = decoded.timestamp();
-            self.decoder
-                .converter(format::Pixel::RGB24)
-                .unwrap()
-                .run(&decoded, &mut rgb_frame)
-                .unwrap();
-            return Some((timestamp, rgb_frame));
+            let (width, height) = (self.width(), self.height());
+            let mut img = RgbImage::new(width, height);
+            // sws_scale reads four planes, the packed RGB image has one
+            let dst = [img.as_mut_ptr(), null_mut(), null_mut(), null_mut()];
+            let dst_stride = [(width * 3) as c_int, 0, 0, 0];
+            unsafe {
+                let src = decoded.as_ptr();
+                ffi::sws_scale(
+                    self.to_rgb.as_mut_ptr(),
+                    (*src).data.as_ptr() as *const *const u8,
+                    (*src).linesize.as_ptr(),
+                    0,
+                    height as _,
+                    dst.as_ptr(),
+                    dst_stride.as_ptr(),
+                );
+            }
+            return Some((timestamp, img));
         }
         None
     }
+}
 
-    fn process_decoded_frames(
-        &mut self,
-        timestamp: Option<i64>,
-        frame: &frame::Video,
-        octx: &mut format::context::Output,
-        ost_time_base: Rational,
-    ) {
-        let mut decoded = frame::Video::empty();
-        software::scaling::Context::get(
-            format::Pixel::RGB24,
-            self.decoder.width(),
-            self.decoder.height(),
-            self.decoder.format(),
-            self.decoder.width(),
-            self.decoder.height(),
-            software::scaling::Flags::FAST_BILINEAR,
-        )
-        .unwrap()
-        .run(frame, &mut decoded)
-        .unwrap();
-        decoded.set_pts(timestamp);
-        decoded.set_kind(picture::Type::None);
-        self.send_frame_to_encoder(&decoded);
-        self.receive_and_process_encoded_packets(octx, ost_time_base);
+struct VideoEncoder {
+    ost_index: usize,
+    encoder: encoder::video::Video,
+    /// Kept for the lifetime of the stream, from RGB to the decoded format
+    from_rgb: software::scaling::Context,
+    format: format::Pixel,
+    width: u32,
+    height: u32,
+    frame_rate: Rational,
+    time_base: Rational,
+    /// The length of a frame in `time_base`, for the frames without a timestamp
+    frame_duration: i64,
+    /// The start of the rendered section in `time_base`, the output begins at zero
+    start: i64,
+    last_pts: Option<i64>,
+}
+
+impl VideoEncoder {
+    #[inline(always)]
+    fn next_pts(&mut self, timestamp: Option<i64>) -> i64 {
+        let pts = increasing_pts(self.last_pts, timestamp, self.frame_duration);
+        self.last_pts = Some(pts);
+        pts
     }
 
-    fn receive_and_process_decoded_frames(
+    /// Scales the image straight into a frame of the decoded format
+    fn process_decoded_frames(
         &mut self,
+        timestamp: Option<i64>,
+        img: &RgbImage,
         octx: &mut format::context::Output,
         ost_time_base: Rational,
-    ) {
-        let mut frame = frame::Video::empty();
-        while self.decoder.receive_frame(&mut frame).is_ok() {
-            let timestamp = frame.timestamp();
-            frame.set_pts(timestamp);
-            frame.set_kind(picture::Type::None);
-            self.send_frame_to_encoder(&frame);
-            self.receive_and_process_encoded_packets(octx, ost_time_base);
+    ) -> Result<(), Error> {
+        let (width, height) = (self.width, self.height);
+        let mut decoded = frame::Video::new(self.format, width, height);
+        // sws_scale reads four planes, the packed RGB image has one
+        let src = [img.as_ptr(), null(), null(), null()];
+        let src_stride = [(width * 3) as c_int, 0, 0, 0];
+        unsafe {
+            let dst = decoded.as_mut_ptr();
+            ffi::sws_scale(
+                self.from_rgb.as_mut_ptr(),
+                src.as_ptr(),
+                src_stride.as_ptr(),
+                0,
+                height as _,
+                (*dst).data.as_ptr(),
+                (*dst).linesize.as_ptr(),
+            );
         }
+        let pts = self.next_pts(timestamp.map(|timestamp| timestamp - self.start));
+        decoded.set_pts(Some(pts));
+        decoded.set_kind(picture::Type::None);
+        self.send_frame_to_encoder(&decoded)?;
+        self.receive_and_process_encoded_packets(octx, ost_time_base)
     }
 
     #[inline(always)]
-    fn send_frame_to_encoder(&mut self, frame: &frame::Video) {
-        self.encoder.send_frame(frame).unwrap();
+    fn send_frame_to_encoder(&mut self, frame: &frame::Video) -> Result<(), Error> {
+        self.encoder.send_frame(frame).map_err(Error::write)
     }
 
     #[inline(always)]
-    fn send_eof_to_encoder(&mut self) {
-        self.encoder.send_eof().unwrap();
+    fn send_eof_to_encoder(&mut self) -> Result<(), Error> {
+        self.encoder.send_eof().map_err(Error::write)
     }
 
     fn receive_and_process_encoded_packets(
         &mut self,
         octx: &mut format::context::Output,
         ost_time_base: Rational,
-    ) {
+    ) -> Result<(), Error> {
         let mut encoded = Packet::empty();
         while self.encoder.receive_packet(&mut encoded).is_ok() {
             encoded.set_stream(self.ost_index);
-            encoded.rescale_ts(self.decoder.time_base(), ost_time_base);
-            encoded.write_interleaved(octx).unwrap();
+            encoded.rescale_ts(self.time_base, ost_time_base);
+            encoded.write_interleaved(octx).map_err(Error::write)?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{estimate_frames, increasing_pts, FALLBACK_FRAME_RATE},
+        ffmpeg::Rational,
+    };
+
+    #[test]
+    fn estimated_frames() {
+        // 10 s at 29.97 fps
+        let ntsc = Rational(30_000, 1001);
+        assert_eq!(estimate_frames(900_000, Rational(1, 90_000), 0, ntsc), 300);
+        // the container lasts 2.4 s
+        let container = 2_400_000;
+        assert_eq!(
+            estimate_frames(0, Rational(1, 90_000), container, FALLBACK_FRAME_RATE),
+            60
+        );
+        // the stream duration is useless without a time base
+        assert_eq!(
+            estimate_frames(900_000, Rational(0, 1), container, FALLBACK_FRAME_RATE),
+            60
+        );
+        assert_eq!(
+            estimate_frames(0, Rational(1, 90_000), 0, FALLBACK_FRAME_RATE),
+            0
+        );
+    }
+
+    #[test]
+    fn increasing_timestamps() {
+        let timestamps = [
+            None,
+            Some(3000),
+            None,
+            Some(3000),
+            Some(1000),
+            None,
+            Some(20_000),
+        ];
+        let mut last = None;
+        let pts = timestamps
+            .into_iter()
+            .map(|timestamp| {
+                let pts = increasing_pts(last, timestamp, 3000);
+                last = Some(pts);
+                pts
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(pts, [0, 3000, 6000, 9000, 12_000, 15_000, 20_000]);
     }
 }
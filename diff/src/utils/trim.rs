@@ -0,0 +1,147 @@
+use crate::ProcessConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Selection {
+    Skip,
+    Take,
+    Stop,
+}
+
+/// Selects the frames of a video to render, the times are in seconds
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Trim {
+    start: f64,
+    end: Option<f64>,
+    step: usize,
+    max_frames: Option<usize>,
+    /// The frames within the range so far
+    count: usize,
+    taken: usize,
+    stopped: bool,
+}
+
+impl Trim {
+    pub(crate) fn new(
+        &ProcessConfig {
+            start,
+            end,
+            frame_step,
+            max_frames,
+            ..
+        }: &ProcessConfig,
+    ) -> Self {
+        Self {
+            start: start.unwrap_or_default().max(0.),
+            end,
+            step: frame_step.max(1),
+            max_frames,
+            count: 0,
+            taken: 0,
+            stopped: false,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn start(&self) -> f64 {
+        self.start
+    }
+
+    #[inline(always)]
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    #[inline(always)]
+    pub(crate) fn contains(&self, time: f64) -> bool {
+        time >= self.start && self.end.map_or(true, |end| time < end)
+    }
+
+    /// A frame without a timestamp is taken as within the range
+    pub(crate) fn select(&mut self, time: Option<f64>) -> Selection {
+        if self.stopped {
+            return Selection::Stop;
+        }
+        match time {
+            Some(time) if time < self.start => return Selection::Skip,
+            Some(time) if self.end.map_or(false, |end| time >= end) => {
+                self.stopped = true;
+                return Selection::Stop;
+            }
+            _ => (),
+        }
+        if self.max_frames.map_or(false, |max| self.taken >= max) {
+            self.stopped = true;
+            return Selection::Stop;
+        }
+
+        let idx = self.count;
+        self.count += 1;
+        if idx % self.step != 0 {
+            return Selection::Skip;
+        }
+        self.taken += 1;
+        Selection::Take
+    }
+
+    /// Estimates the number of frames taken out of `frames` at `frame_rate`
+    pub(crate) fn frames(&self, frames: i64, frame_rate: f64) -> i64 {
+        let mut frames = frames;
+        if frame_rate > 0. {
+            let duration = frames as f64 / frame_rate;
+            let end = self.end.map_or(duration, |end| end.min(duration));
+            frames = ((end - self.start).max(0.) * frame_rate).round() as i64;
+        }
+        let step = self.step as i64;
+        frames = (frames + step - 1) / step;
+        match self.max_frames {
+            Some(max) => frames.min(max as i64),
+            None => frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Selection, Trim},
+        crate::ProcessConfig,
+    };
+
+    fn new(start: Option<f64>, end: Option<f64>, frame_step: usize, max: Option<usize>) -> Trim {
+        Trim::new(&ProcessConfig {
+            start,
+            end,
+            frame_step,
+            max_frames: max,
+            ..Default::default()
+        })
+    }
+
+    fn select(mut trim: Trim, frames: usize) -> Vec<Selection> {
+        (0..frames)
+            .map(|i| trim.select(Some(i as f64 / 10.)))
+            .collect()
+    }
+
+    #[test]
+    fn range() {
+        use Selection::*;
+
+        let trim = new(Some(0.2), Some(0.5), 1, None);
+        assert_eq!(select(trim, 7), [Skip, Skip, Take, Take, Take, Stop, Stop]);
+        assert_eq!(trim.frames(100, 10.), 3);
+    }
+
+    #[test]
+    fn step_and_max_frames() {
+        use Selection::*;
+
+        let trim = new(None, None, 3, Some(2));
+        assert_eq!(
+            select(trim, 8),
+            [Take, Skip, Skip, Take, Stop, Stop, Stop, Stop]
+        );
+        assert_eq!(trim.frames(100, 10.), 2);
+        assert_eq!(new(None, None, 3, None).frames(100, 0.), 34);
+    }
+}
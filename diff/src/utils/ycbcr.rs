@@ -0,0 +1,95 @@
+use {
+    super::{MyLab, MySrgb},
+    palette::{
+        convert::{FromColorUnclamped, IntoColorUnclamped},
+        Clamp, Pixel,
+    },
+};
+
+/// Full-range YCbCr with ITU-R BT.601 coefficients, as used by JPEG
+/// See [YCbCr](https://en.wikipedia.org/wiki/YCbCr#JPEG_conversion)
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub(crate) struct YCbCr {
+    pub(crate) y: f32,
+    pub(crate) cb: f32,
+    pub(crate) cr: f32,
+}
+
+unsafe impl Pixel<f32> for YCbCr {
+    const CHANNELS: usize = 3;
+}
+
+impl FromColorUnclamped<MySrgb> for YCbCr {
+    #[inline(always)]
+    fn from_color_unclamped(rgb: MySrgb) -> Self {
+        let MySrgb {
+            red, green, blue, ..
+        } = rgb;
+        Self {
+            y: 0.299 * red + 0.587 * green + 0.114 * blue,
+            cb: -0.168_736 * red - 0.331_264 * green + 0.5 * blue,
+            cr: 0.5 * red - 0.418_688 * green - 0.081_312 * blue,
+        }
+    }
+}
+
+impl FromColorUnclamped<YCbCr> for MySrgb {
+    #[inline(always)]
+    fn from_color_unclamped(YCbCr { y, cb, cr }: YCbCr) -> Self {
+        Self::new(
+            y + 1.402 * cr,
+            y - 0.344_136 * cb - 0.714_136 * cr,
+            y + 1.772 * cb,
+        )
+    }
+}
+
+impl FromColorUnclamped<YCbCr> for MyLab {
+    #[inline(always)]
+    fn from_color_unclamped(color: YCbCr) -> Self {
+        MySrgb::from_color_unclamped(color).into_color_unclamped()
+    }
+}
+
+impl Clamp for YCbCr {
+    #[inline(always)]
+    fn is_within_bounds(&self) -> bool {
+        (0. ..=1.).contains(&self.y)
+            && (-0.5..=0.5).contains(&self.cb)
+            && (-0.5..=0.5).contains(&self.cr)
+    }
+
+    #[inline(always)]
+    fn clamp(&self) -> Self {
+        let mut c = *self;
+        c.clamp_self();
+        c
+    }
+
+    #[inline(always)]
+    fn clamp_self(&mut self) {
+        self.y = self.y.clamp(0., 1.);
+        self.cb = self.cb.clamp(-0.5, 0.5);
+        self.cr = self.cr.clamp(-0.5, 0.5);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::YCbCr,
+        crate::MySrgb,
+        palette::{convert::FromColorUnclamped, Pixel, Srgb},
+    };
+
+    #[test]
+    fn round_trip() {
+        for raw in [[0u8, 0, 0], [255, 255, 255], [255, 0, 0], [12, 200, 99]] {
+            let rgb = Srgb::from_raw(&raw).into_format::<f32>();
+            let back = MySrgb::from_color_unclamped(YCbCr::from_color_unclamped(rgb));
+            let back: [u8; 3] = back.into_format::<u8>().into_raw();
+            assert_eq!(raw, back);
+        }
+    }
+}
@@ -18,13 +18,14 @@ use {
     std::{
         borrow::Cow,
         ffi::OsStr,
-        fs::{create_dir, read_dir, remove_dir},
+        fs::{create_dir, remove_dir},
         path::{Path, PathBuf},
     },
     steps::{StepMessage, Steps, TargetType},
     streams::{crawler, process},
     styles::{fonts, spacings, Theme},
-    video_mosaic_diff::{first_frame, IMAGE_FILTER, VIDEO_FILTER},
+    video_mosaic_crawler::{DownloadConfig, Limiter, SourceConfig},
+    video_mosaic_diff::{first_frame, scan_library, LibraryConfig, IMAGE_FILTER, VIDEO_FILTER},
     widgets::{pri_btn, rou_btn, sec_btn},
 };
 
@@ -193,12 +194,39 @@ impl<'a> Application for VideoMosaic<'a> {
                         };
                         create_dir(&folder).unwrap();
                         state.crawler_id += 1;
+                        let optional = |text: &str| {
+                            Some(text.trim())
+                                .filter(|text| !text.is_empty())
+                                .map(String::from)
+                        };
+                        let config = SourceConfig {
+                            url: optional(&state.source_url),
+                            key: optional(&state.source_key),
+                            json_path: optional(&state.json_path),
+                            ..SourceConfig::default()
+                        };
+                        let limiter = state
+                            .limiter
+                            .get_or_insert_with(|| Limiter::new(DownloadConfig::default()))
+                            .clone();
                         state.crawlers.insert(
                             state.crawler_id,
-                            crawler::Crawler::new(state.crawler_id, keyword, num, folder),
+                            crawler::Crawler::new(
+                                state.crawler_id,
+                                keyword,
+                                num,
+                                folder,
+                                state.source,
+                                config,
+                                limiter,
+                            ),
                         );
                     }
                 }
+                StepMessage::Source(source) => state.source = source,
+                StepMessage::EditSourceUrl(text) => state.source_url = text,
+                StepMessage::EditSourceKey(text) => state.source_key = text,
+                StepMessage::EditJsonPath(text) => state.json_path = text,
                 StepMessage::DeleteCrawler(idx) => {
                     state.pending.remove(idx);
                 }
@@ -208,6 +236,11 @@ impl<'a> Application for VideoMosaic<'a> {
                             v.add();
                         }
                     }
+                    crawler::Progress::Rejected(id, reason) => {
+                        if let Some(v) = state.crawlers.get_mut(&id) {
+                            v.reject(reason);
+                        }
+                    }
                     crawler::Progress::Finished(id) => {
                         if let Some(v) = state.crawlers.remove(&id) {
                             self.add_library(v.folder());
@@ -237,6 +270,8 @@ impl<'a> Application for VideoMosaic<'a> {
                     state.config.overlay = if item { Some(127) } else { None }
                 }
                 StepMessage::OverlayValue(item) => state.config.overlay = Some(item),
+                StepMessage::Dedup(item) => state.config.dedup = if item { Some(4) } else { None },
+                StepMessage::DedupValue(item) => state.config.dedup = Some(item as u32),
 
                 StepMessage::Start => {
                     let video = state.target_type == TargetType::Video;
@@ -266,7 +301,7 @@ impl<'a> Application for VideoMosaic<'a> {
                     state.clear();
                     state.result_path = path;
                     state.process = Some(process::Process::new(
-                        state.config,
+                        state.config.clone(),
                         state.target_path.to_string_lossy().to_string(),
                         state.result_path.to_string_lossy().to_string(),
                         video,
@@ -275,14 +310,22 @@ impl<'a> Application for VideoMosaic<'a> {
                 }
 
                 StepMessage::ProcessMessage(ev) => match ev {
-                    process::Progress::Started(a, b, c) => {
+                    process::Progress::Started(a, b, c, warnings) => {
                         state.step[0] = 100. / a;
                         state.step[1] = 100. / b;
                         state.step[2] = 100. / c;
+                        state.warnings = warnings;
+                    }
+                    process::Progress::Indexing(len) => {
+                        state.percentage[0] += state.step[0] * len as f32
+                    }
+                    process::Progress::Indexed(removed) => {
+                        state.percentage[0] = 100.;
+                        state.removed = Some(removed);
+                    }
+                    process::Progress::Filling(len) => {
+                        state.percentage[1] += state.step[1] * len as f32
                     }
-                    process::Progress::Indexing => state.percentage[0] += state.step[0],
-                    process::Progress::Indexed => state.percentage[0] = 100.,
-                    process::Progress::Filling => state.percentage[1] += state.step[1],
                     process::Progress::Filled => {
                         state.percentage[1] = 0.;
                         state.percentage[2] += state.step[2];
@@ -295,7 +338,10 @@ impl<'a> Application for VideoMosaic<'a> {
                         state.result_preview =
                             path2handle(&state.result_path, state.target_type == TargetType::Video);
                     }
-                    process::Progress::Error => error_dialog(state.i18n.error, "".into()),
+                    process::Progress::Error(err) => {
+                        error_dialog(state.i18n.error, err.into());
+                        state.process = None;
+                    }
                     process::Progress::None => (),
                 },
             },
@@ -419,29 +465,18 @@ impl<'a> Application for VideoMosaic<'a> {
 impl VideoMosaic<'_> {
     fn add_library(&mut self, path: &Path) {
         let Self { state, .. } = self;
-        let entries = match read_dir(path) {
+        // the files of the chosen folder only
+        let config = LibraryConfig {
+            max_depth: Some(0),
+            ..LibraryConfig::default()
+        };
+        let entries = match scan_library(&[path.to_path_buf()], &config) {
             Ok(entries) => entries,
             Err(err) => {
                 error_dialog(state.i18n.error, err.to_string().into());
                 return;
             }
         };
-        let entries = entries
-            .flatten()
-            .filter_map(|entry| {
-                let path = entry.path();
-                let ext = path
-                    .extension()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or_default();
-                if path.is_file() && IMAGE_FILTER.contains(&ext) {
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
         if !entries.is_empty() {
             state.libraries.insert(path.to_path_buf(), entries);
         }
@@ -5,8 +5,9 @@ use {
         styles::Theme,
     },
     iced::image::Handle,
-    video_mosaic_diff::ProcessConfig,
-    std::{collections::HashMap, path::PathBuf},
+    video_mosaic_crawler::{Limiter, Source},
+    video_mosaic_diff::{ProcessConfig, Warning},
+    std::{collections::HashMap, path::PathBuf, sync::Arc},
 };
 
 pub const LIBRARY_BTN_CNT: usize = 16;
@@ -22,14 +23,24 @@ pub struct State {
 
     pub libraries: HashMap<PathBuf, Vec<PathBuf>>,
     pub pending: Vec<(String, String)>,
+    pub source: Source,
+    pub source_url: String,
+    pub source_key: String,
+    pub json_path: String,
     pub crawler_id: usize,
     pub crawlers: HashMap<usize, crawler::Crawler>,
+    /// Shared by all the crawlers, so that together they keep to the rate of each host
+    pub limiter: Option<Arc<Limiter>>,
 
     pub config: ProcessConfig,
 
     pub step: [f32; 3],
     pub percentage: [f32; 3],
     pub process: Option<process::Process>,
+    /// The number of near duplicates removed from the library by the last process
+    pub removed: Option<usize>,
+    /// What the output of the last process leaves out of the input
+    pub warnings: Vec<Warning>,
     pub result_path: PathBuf,
     pub result_preview: Option<Handle>,
 }
@@ -45,6 +56,8 @@ impl State {
         self.percentage[0] = 0.;
         self.percentage[1] = 0.;
         self.percentage[2] = 0.;
+        self.removed = None;
+        self.warnings.clear();
         self.result_preview = None;
     }
 }
@@ -68,6 +81,10 @@ pub struct I18n {
     pub delete: &'static str,
     pub delete_desc: &'static str,
     pub keyword_prompt: &'static str,
+    pub source_url_prompt: &'static str,
+    pub source_key_prompt: &'static str,
+    pub json_path_prompt: &'static str,
+    pub rejected: &'static str,
 
     pub choose_method: &'static str,
     pub calc_unit: &'static str,
@@ -86,6 +103,7 @@ pub struct I18n {
     pub size: &'static str,
     pub quad: &'static str,
     pub overlay: &'static str,
+    pub dedup: &'static str,
 
     pub process_preview: &'static str,
     pub start: &'static str,
@@ -93,6 +111,8 @@ pub struct I18n {
     pub fill: &'static str,
     pub composite: &'static str,
     pub saved_to_local: &'static str,
+    pub removed: &'static str,
+    pub warning: &'static str,
 }
 
 impl Default for &I18n {
@@ -120,6 +140,10 @@ pub const EN: I18n = I18n {
     delete: "Delete",
     delete_desc: "Are you sure to delete?",
     keyword_prompt: "Please enter keyword.",
+    source_url_prompt: "Base URL, URL template or manifest path",
+    source_key_prompt: "API key (optional)",
+    json_path_prompt: "JSONPath of the image URLs",
+    rejected: "rejected",
 
     choose_method: "Choose Method",
     calc_unit: "Calculation Unit",
@@ -138,6 +162,7 @@ pub const EN: I18n = I18n {
     size: "Size",
     quad: "Quad",
     overlay: "Overlay",
+    dedup: "Dedup",
 
     process_preview: "Process & Preview",
     start: "Start",
@@ -145,6 +170,8 @@ pub const EN: I18n = I18n {
     fill: "Fill",
     composite: "Composite",
     saved_to_local: "Saved to local",
+    removed: "Near duplicates removed from the library",
+    warning: "Warning",
 };
 
 pub const ZH_CN: I18n = I18n {
@@ -166,6 +193,10 @@ pub const ZH_CN: I18n = I18n {
     delete: "删除",
     delete_desc: "你确定要删除吗？",
     keyword_prompt: "请输入关键词",
+    source_url_prompt: "基础 URL、URL 模板或清单路径",
+    source_key_prompt: "API 密钥（可选）",
+    json_path_prompt: "图片 URL 的 JSONPath",
+    rejected: "已拒绝",
 
     choose_method: "选择方案",
     calc_unit: "计算单位",
@@ -184,6 +215,7 @@ pub const ZH_CN: I18n = I18n {
     size: "大小",
     quad: "四分图",
     overlay: "层叠",
+    dedup: "去重",
 
     process_preview: "处理并预览",
     start: "开始",
@@ -191,4 +223,6 @@ pub const ZH_CN: I18n = I18n {
     fill: "填充图片",
     composite: "合成",
     saved_to_local: "保存至本地",
+    removed: "已从图库中移除的近似重复图片",
+    warning: "警告",
 };
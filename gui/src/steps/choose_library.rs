@@ -6,9 +6,10 @@ use {
         widgets::{pri_btn, rou_btn, tra_btn},
     },
     iced::{
-        button, scrollable, text_input, Column, Container, Element, Length, ProgressBar, Row,
-        Scrollable, Subscription, Text, TextInput,
+        button, scrollable, text_input, Column, Container, Element, Length, ProgressBar, Radio,
+        Row, Scrollable, Subscription, Text, TextInput,
     },
+    video_mosaic_crawler::Source,
 };
 
 #[derive(Default, Clone)]
@@ -17,6 +18,9 @@ pub struct ChooseLibrary {
     right_scroll: scrollable::State,
     local_btn: button::State,
     spider_btn: button::State,
+    source_url_input: text_input::State,
+    source_key_input: text_input::State,
+    json_path_input: text_input::State,
     library_btn: [button::State; LIBRARY_BTN_CNT],
     keyword_input: [text_input::State; LIBRARY_BTN_CNT],
     number_input: [text_input::State; LIBRARY_BTN_CNT],
@@ -51,6 +55,9 @@ impl<'a> Step<'a> for ChooseLibrary {
             right_scroll,
             local_btn,
             spider_btn,
+            source_url_input,
+            source_key_input,
+            json_path_input,
             library_btn,
             keyword_input,
             number_input,
@@ -103,11 +110,23 @@ impl<'a> Step<'a> for ChooseLibrary {
             );
 
         let left_side = state.crawlers.values().fold(left_side, |scroll, crawler| {
-            scroll.push(
+            let scroll = scroll.push(
                 ProgressBar::new(1.0..=100.0, crawler.percentage())
                     .height(Length::Units(spacings::_10))
                     .style(state.theme),
-            )
+            );
+            match crawler.rejections().last() {
+                Some(reason) => scroll.push(
+                    Text::new(format!(
+                        "{} {}: {}",
+                        crawler.rejections().len(),
+                        state.i18n.rejected,
+                        reason
+                    ))
+                    .size(spacings::_6),
+                ),
+                None => scroll,
+            }
         });
 
         let left_side = state.libraries.keys().zip(library_btn.iter_mut()).fold(
@@ -156,9 +175,59 @@ impl<'a> Step<'a> for ChooseLibrary {
                     .width(Length::FillPortion(1))
                     .on_press(StepMessage::AddCrawler),
             );
+        let source_ctl =
+            Source::ALL
+                .into_iter()
+                .fold(Row::new().spacing(spacings::_8), |row, item| {
+                    row.push(
+                        Radio::new(item, item, Some(state.source), StepMessage::Source)
+                            .style(state.theme),
+                    )
+                });
+        let source_inputs = Row::new()
+            .spacing(spacings::_2)
+            .push(
+                TextInput::new(
+                    source_url_input,
+                    state.i18n.source_url_prompt,
+                    state.source_url.as_str(),
+                    StepMessage::EditSourceUrl,
+                )
+                .width(Length::FillPortion(1))
+                .style(state.theme)
+                .padding(spacings::_1)
+                .size(spacings::_8),
+            )
+            .push(
+                TextInput::new(
+                    source_key_input,
+                    state.i18n.source_key_prompt,
+                    state.source_key.as_str(),
+                    StepMessage::EditSourceKey,
+                )
+                .password()
+                .width(Length::FillPortion(1))
+                .style(state.theme)
+                .padding(spacings::_1)
+                .size(spacings::_8),
+            )
+            .push(
+                TextInput::new(
+                    json_path_input,
+                    state.i18n.json_path_prompt,
+                    state.json_path.as_str(),
+                    StepMessage::EditJsonPath,
+                )
+                .width(Length::FillPortion(1))
+                .style(state.theme)
+                .padding(spacings::_1)
+                .size(spacings::_8),
+            );
         let left_side = Column::new()
             .spacing(spacings::_8)
             .push(left_ctl)
+            .push(source_ctl)
+            .push(source_inputs)
             .push(left_side);
 
         Row::new()
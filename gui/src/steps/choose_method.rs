@@ -15,6 +15,7 @@ pub struct ChooseMethod {
     size_slider: slider::State,
     quad_slider: slider::State,
     overlay_slider: slider::State,
+    dedup_slider: slider::State,
 }
 
 impl<'a> Step<'a> for ChooseMethod {
@@ -29,6 +30,7 @@ impl<'a> Step<'a> for ChooseMethod {
             size_slider,
             quad_slider,
             overlay_slider,
+            dedup_slider,
         } = self;
 
         let calc_unit = [
@@ -54,43 +56,59 @@ impl<'a> Step<'a> for ChooseMethod {
             },
         );
 
-        let color_space = [ColorSpace::RGB, ColorSpace::HSV, ColorSpace::CIELAB]
-            .into_iter()
-            .fold(
-                Column::new()
-                    .spacing(spacings::_6)
-                    .push(Text::new(state.i18n.color_space).size(spacings::_8)),
-                |col, item| {
-                    col.push(
-                        Radio::new(
-                            item,
-                            item,
-                            Some(state.config.color_space),
-                            StepMessage::ColorSpace,
-                        )
-                        .style(state.theme),
+        let color_space = [
+            ColorSpace::RGB,
+            ColorSpace::HSV,
+            ColorSpace::CIELAB,
+            ColorSpace::CIELCH,
+            ColorSpace::OKLAB,
+            ColorSpace::YCBCR,
+            ColorSpace::XYZ,
+        ]
+        .into_iter()
+        .fold(
+            Column::new()
+                .spacing(spacings::_6)
+                .push(Text::new(state.i18n.color_space).size(spacings::_8)),
+            |col, item| {
+                col.push(
+                    Radio::new(
+                        item,
+                        item,
+                        Some(state.config.color_space),
+                        StepMessage::ColorSpace,
                     )
-                },
-            );
+                    .style(state.theme),
+                )
+            },
+        );
 
-        let dist_algo = [DistanceAlgorithm::Euclidean, DistanceAlgorithm::CIEDE2000]
-            .into_iter()
-            .fold(
-                Column::new()
-                    .spacing(spacings::_6)
-                    .push(Text::new(state.i18n.dist_algo).size(spacings::_8)),
-                |col, item| {
-                    col.push(
-                        Radio::new(
-                            item,
-                            item,
-                            Some(state.config.dist_algo),
-                            StepMessage::DistanceAlgorithm,
-                        )
-                        .style(state.theme),
+        let dist_algo = [
+            DistanceAlgorithm::Euclidean,
+            DistanceAlgorithm::WeightedEuclidean,
+            DistanceAlgorithm::CIE76,
+            DistanceAlgorithm::CIE94GraphicArts,
+            DistanceAlgorithm::CIE94Textiles,
+            DistanceAlgorithm::CMC,
+            DistanceAlgorithm::CIEDE2000,
+        ]
+        .into_iter()
+        .fold(
+            Column::new()
+                .spacing(spacings::_6)
+                .push(Text::new(state.i18n.dist_algo).size(spacings::_8)),
+            |col, item| {
+                col.push(
+                    Radio::new(
+                        item,
+                        item,
+                        Some(state.config.dist_algo),
+                        StepMessage::DistanceAlgorithm,
                     )
-                },
-            );
+                    .style(state.theme),
+                )
+            },
+        );
 
         let filter = [
             Filter::Nearest,
@@ -140,6 +158,7 @@ impl<'a> Step<'a> for ChooseMethod {
 
         let quad_iter = state.config.quad_iter.unwrap_or(500) as u16;
         let overlay = state.config.overlay.unwrap_or(127);
+        let dedup = state.config.dedup.unwrap_or(4) as u8;
         let config = Column::new()
             .spacing(spacings::_6)
             .push(Text::new(state.i18n.configuration).size(spacings::_8))
@@ -198,6 +217,23 @@ impl<'a> Step<'a> for ChooseMethod {
                         .width(Length::Fill)
                         .style(state.theme),
                     ),
+            )
+            .push(
+                Row::new()
+                    .spacing(spacings::_6)
+                    .push(
+                        Checkbox::new(
+                            state.config.dedup.is_some(),
+                            format!("{}: {}", state.i18n.dedup, dedup),
+                            StepMessage::Dedup,
+                        )
+                        .style(state.theme),
+                    )
+                    .push(
+                        Slider::new(dedup_slider, 0..=16, dedup, StepMessage::DedupValue)
+                            .width(Length::Fill)
+                            .style(state.theme),
+                    ),
             );
 
         let l = Length::FillPortion(1);
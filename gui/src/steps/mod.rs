@@ -12,6 +12,7 @@ use {
     choose_method::ChooseMethod,
     choose_target::ChooseTarget,
     iced::{Element, Subscription},
+    video_mosaic_crawler::Source,
     video_mosaic_diff::{CalculationUnit, ColorSpace, DistanceAlgorithm, Filter},
     process_preview::ProcessPreview,
     std::path::PathBuf,
@@ -28,6 +29,10 @@ pub enum StepMessage {
     EditKeyword(usize, String),
     EditNumber(usize, String),
     StartCrawler(usize),
+    Source(Source),
+    EditSourceUrl(String),
+    EditSourceKey(String),
+    EditJsonPath(String),
     DeleteCrawler(usize),
     CrawlerMessage(crawler::Progress),
     CalculationUnit(CalculationUnit),
@@ -41,6 +46,8 @@ pub enum StepMessage {
     QuadValue(u16),
     Overlay(bool),
     OverlayValue(u8),
+    Dedup(bool),
+    DedupValue(u8),
     Start,
     ProcessMessage(process::Progress),
 }
@@ -80,6 +80,14 @@ impl<'a> Step<'a> for ProcessPreview {
             .align_items(Alignment::Center)
             .style(state.theme);
 
+        if let Some(removed) = state.removed.filter(|removed| *removed > 0) {
+            container = container.push(Text::new(format!("{}: {}", state.i18n.removed, removed)));
+        }
+
+        for warning in &state.warnings {
+            container = container.push(Text::new(format!("{}: {}", state.i18n.warning, warning)));
+        }
+
         if let Some(img) = state.result_preview.as_ref() {
             container = container
                 .push(Image::new(img.clone()).width(Length::Fill))
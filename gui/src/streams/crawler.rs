@@ -4,7 +4,10 @@ use {
         Subscription,
     },
     iced_native::subscription,
-    video_mosaic_crawler::{download_urls, gen_client, get_urls, HttpClient, Result, TasksIter},
+    video_mosaic_crawler::{
+        download_urls, gen_client, get_urls, Download, Found, HttpClient, ImageSource, Limiter,
+        Result, Source, SourceConfig, TasksIter,
+    },
     std::{
         any::TypeId,
         hash::{Hash, Hasher},
@@ -21,11 +24,23 @@ pub struct Crawler {
     step: f32,
     percentage: f32,
     folder: PathBuf,
+    source: Source,
+    config: SourceConfig,
+    limiter: Arc<Limiter>,
+    rejections: Vec<String>,
 }
 
 impl Crawler {
     #[inline(always)]
-    pub fn new(id: usize, keyword: String, num: usize, folder: PathBuf) -> Self {
+    pub fn new(
+        id: usize,
+        keyword: String,
+        num: usize,
+        folder: PathBuf,
+        source: Source,
+        config: SourceConfig,
+        limiter: Arc<Limiter>,
+    ) -> Self {
         Self {
             id,
             keyword,
@@ -33,6 +48,10 @@ impl Crawler {
             step: 100. / num as f32,
             percentage: 0.,
             folder,
+            source,
+            config,
+            limiter,
+            rejections: Vec::new(),
         }
     }
 
@@ -46,6 +65,17 @@ impl Crawler {
         self.percentage += self.step;
     }
 
+    #[inline(always)]
+    pub fn reject(&mut self, reason: String) {
+        self.percentage += self.step;
+        self.rejections.push(reason);
+    }
+
+    #[inline(always)]
+    pub fn rejections(&self) -> &[String] {
+        &self.rejections
+    }
+
     #[inline(always)]
     pub fn percentage(&self) -> f32 {
         self.percentage
@@ -73,43 +103,84 @@ where
         let keyword = self.keyword;
         let num = self.num;
         let folder = self.folder;
+        let source = self.source;
+        let config = self.config;
+        let limiter = self.limiter;
 
         Box::pin(unfold(
-            State::Ready(gen_client(), keyword, num, folder),
+            State::Ready(source, config, limiter, keyword, num, folder),
             move |state| async move {
                 match state {
-                    State::Ready(client, keyword, num, folder) => {
-                        Some(match get_urls(client.clone(), keyword, num).await {
-                            Ok((num, tasks)) => {
+                    State::Ready(source, config, limiter, keyword, num, folder) => {
+                        let res = async {
+                            let client = gen_client()?;
+                            let source = source.build(&config)?;
+                            let (num, tasks) =
+                                get_urls(client.clone(), source.clone(), keyword.clone(), num)
+                                    .await?;
+                            Result::<_>::Ok((client, source, num, tasks))
+                        }
+                        .await;
+                        Some(match res {
+                            Ok((client, source, num, tasks)) => {
                                 let tasks = tasks.into_iter();
                                 let urls = Vec::with_capacity(num);
-                                (Progress::None, State::Getting(client, tasks, urls, folder))
+                                let state = State::Getting(
+                                    client, source, limiter, tasks, keyword, urls, folder,
+                                );
+                                (Progress::None, state)
                             }
                             Err(e) => (Progress::Error(id, e.to_string()), State::Finished),
                         })
                     }
 
-                    State::Getting(client, mut tasks, mut urls, folder) => {
-                        Some(match tasks.next() {
-                            Some(task) => {
-                                if let Ok(ret) = task.await {
-                                    urls.extend_from_slice(&ret);
-                                }
-                                (Progress::None, State::Getting(client, tasks, urls, folder))
+                    State::Getting(
+                        client,
+                        source,
+                        limiter,
+                        mut tasks,
+                        keyword,
+                        mut urls,
+                        folder,
+                    ) => Some(match tasks.next() {
+                        Some(task) => {
+                            if let Ok(ret) = task.await {
+                                urls.extend_from_slice(&ret);
                             }
-                            None => {
-                                let tasks = download_urls(client, urls, folder.clone()).into_iter();
+                            let state = State::Getting(
+                                client, source, limiter, tasks, keyword, urls, folder,
+                            );
+                            (Progress::None, state)
+                        }
+                        None => match download_urls(
+                            client,
+                            source,
+                            limiter,
+                            &keyword,
+                            urls,
+                            folder.clone(),
+                        ) {
+                            Ok(tasks) => {
+                                let tasks = tasks.into_iter();
                                 (Progress::None, State::Downloading(tasks, false))
                             }
-                        })
-                    }
+                            Err(e) => (Progress::Error(id, e.to_string()), State::Finished),
+                        },
+                    }),
 
                     State::Downloading(mut tasks, mut flag) => Some(match tasks.next() {
                         Some(task) => {
-                            if let Ok(true) = task.await {
-                                flag = true;
-                            }
-                            (Progress::Downloading(id), State::Downloading(tasks, flag))
+                            let progress = match task.await {
+                                Ok(Download::Saved) => {
+                                    flag = true;
+                                    Progress::Downloading(id)
+                                }
+                                Ok(Download::Rejected(rejection)) => {
+                                    Progress::Rejected(id, rejection.to_string())
+                                }
+                                Err(_) => Progress::Downloading(id),
+                            };
+                            (progress, State::Downloading(tasks, flag))
                         }
                         None => (
                             if flag {
@@ -131,6 +202,8 @@ where
 #[derive(Debug, Clone)]
 pub enum Progress {
     Downloading(usize),
+    /// The reason why a download is not kept
+    Rejected(usize, String),
     Finished(usize),
     None,
     Error(usize, String),
@@ -138,13 +211,16 @@ pub enum Progress {
 
 #[derive(Debug)]
 enum State {
-    Ready(Arc<HttpClient>, String, usize, PathBuf),
+    Ready(Source, SourceConfig, Arc<Limiter>, String, usize, PathBuf),
     Getting(
         Arc<HttpClient>,
-        TasksIter<Result<Vec<String>>>,
-        Vec<String>,
+        Arc<dyn ImageSource>,
+        Arc<Limiter>,
+        TasksIter<Result<Vec<Found>>>,
+        String,
+        Vec<Found>,
         PathBuf,
     ),
-    Downloading(TasksIter<Result<bool>>, bool),
+    Downloading(TasksIter<Result<Download>>, bool),
     Finished,
 }
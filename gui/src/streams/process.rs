@@ -4,8 +4,7 @@ use {
         Subscription,
     },
     iced_native::subscription,
-    image::RgbImage,
-    video_mosaic_diff::{LibItem, Mask, ProcessConfig, ProcessWrapper, RawColor, TasksIter},
+    video_mosaic_diff::{Error, Indexed, Mask, ProcessConfig, ProcessWrapper, TasksIter, Warning},
     std::{
         any::TypeId,
         cell::Cell,
@@ -59,69 +58,92 @@ where
             State::Ready(config, input, output, video, library),
             move |state| async move {
                 match state {
-                    State::Ready(config, input, output, video, library) => Some({
-                        let proc = ProcessWrapper::new(config, input, output, video);
+                    State::Ready(config, input, output, video, library) => {
                         let size = config.size as u32;
-                        (
-                            Progress::Started(
-                                library.len() as f32,
-                                ((proc.width() / size + 1) * (proc.height() / size + 1)) as f32,
-                                proc.frames() as f32,
-                            ),
-                            State::Start(proc, library),
-                        )
-                    }),
+                        Some(match ProcessWrapper::new(config, input, output, video) {
+                            Ok(proc) => {
+                                (
+                                    Progress::Started(
+                                        library.len() as f32,
+                                        ((proc.width() / size + 1) * (proc.height() / size + 1))
+                                            as f32,
+                                        proc.frames() as f32,
+                                        proc.warnings().to_vec(),
+                                    ),
+                                    State::Start(proc, library),
+                                )
+                            }
+                            Err(e) => (Progress::Error(e.to_string()), State::Finished),
+                        })
+                    }
 
                     State::Start(proc, library) => Some({
-                        let lib_color = Vec::with_capacity(library.len());
-                        let lib_image = Vec::with_capacity(library.len());
                         let tasks = proc.index(library).into_iter();
-                        (
-                            Progress::None,
-                            State::Indexing(proc, tasks, lib_color, lib_image),
-                        )
+                        (Progress::None, State::Indexing(proc, tasks, Vec::new()))
                     }),
 
-                    State::Indexing(mut proc, mut tasks, mut lib_color, mut lib_image) => {
+                    State::Indexing(mut proc, mut tasks, mut chunks) => {
                         Some(match tasks.next() {
-                            Some(task) => {
-                                if let Some((color, image)) = task.await {
-                                    lib_color.push(color);
-                                    lib_image.push(image);
-                                }
-                                (
-                                    Progress::Indexing,
-                                    State::Indexing(proc, tasks, lib_color, lib_image),
-                                )
-                            }
-                            None => match lib_image.is_empty() {
-                                true => (Progress::Error, State::Finished),
-                                false => {
-                                    proc.post_index(lib_color, lib_image);
-                                    let _ = proc.pre_fill();
-                                    let tasks = proc.fill().into_iter();
-                                    (Progress::Indexed, State::Filling(proc, tasks))
+                            Some(task) => match task.await {
+                                Ok(chunk) => {
+                                    let len = chunk.1.len();
+                                    chunks.push(chunk);
+                                    (
+                                        Progress::Indexing(len),
+                                        State::Indexing(proc, tasks, chunks),
+                                    )
                                 }
+                                Err(e) => (Progress::Error(e.to_string()), State::Finished),
                             },
+                            None => {
+                                // the same library in the same order with the same seed
+                                chunks.sort_unstable_by_key(|(idx, _)| *idx);
+                                let (lib_color, lib_image): (Vec<_>, Vec<_>) = chunks
+                                    .into_iter()
+                                    .flat_map(|(_, items)| items.into_iter().flatten())
+                                    .unzip();
+                                match lib_image.is_empty() {
+                                    true => (Progress::Error(String::new()), State::Finished),
+                                    false => {
+                                        let filled = proc
+                                            .post_index(lib_color, lib_image)
+                                            .and_then(|removed| Ok((removed, proc.pre_fill()?)));
+                                        match filled {
+                                            Ok((removed, true)) => {
+                                                let tasks = proc.fill().into_iter();
+                                                let progress = Progress::Indexed(removed);
+                                                (progress, State::Filling(proc, tasks))
+                                            }
+                                            Ok((_, false)) => (Progress::Finished, State::Finished),
+                                            Err(e) => {
+                                                (Progress::Error(e.to_string()), State::Finished)
+                                            }
+                                        }
+                                    }
+                                }
+                            }
                         })
                     }
 
                     State::Filling(mut proc, mut tasks) => Some(match tasks.next() {
-                        Some(task) => {
-                            let (mask, replace_idx) = task.await;
-                            proc.post_fill_step(mask, replace_idx);
-                            (Progress::Filling, State::Filling(proc, tasks))
-                        }
-                        None => {
-                            proc.post_fill();
-                            match proc.pre_fill() {
-                                true => {
-                                    let tasks = proc.fill().into_iter();
-                                    (Progress::Filled, State::Filling(proc, tasks))
+                        Some(task) => match task.await {
+                            Ok(items) => {
+                                let len = items.len();
+                                for (mask, replace_idx) in items {
+                                    proc.post_fill_step(mask, replace_idx);
                                 }
-                                false => (Progress::Finished, State::Finished),
+                                (Progress::Filling(len), State::Filling(proc, tasks))
+                            }
+                            Err(e) => (Progress::Error(e.to_string()), State::Finished),
+                        },
+                        None => match proc.post_fill().and_then(|_| proc.pre_fill()) {
+                            Ok(true) => {
+                                let tasks = proc.fill().into_iter();
+                                (Progress::Filled, State::Filling(proc, tasks))
                             }
-                        }
+                            Ok(false) => (Progress::Finished, State::Finished),
+                            Err(e) => (Progress::Error(e.to_string()), State::Finished),
+                        },
                     }),
 
                     State::Finished => None,
@@ -131,15 +153,17 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Progress {
-    Started(f32, f32, f32),
-    Indexing,
-    Indexed,
-    Filling,
+    /// The numbers of library images, masks and frames, and what the output leaves out
+    Started(f32, f32, f32, Vec<Warning>),
+    Indexing(usize),
+    /// The number of near duplicates removed from the library
+    Indexed(usize),
+    Filling(usize),
     Filled,
     Finished,
-    Error,
+    Error(String),
     None,
 }
 
@@ -149,10 +173,9 @@ enum State {
     Start(ProcessWrapper, Vec<PathBuf>),
     Indexing(
         ProcessWrapper,
-        TasksIter<Option<LibItem>>,
-        Vec<RawColor>,
-        Vec<RgbImage>,
+        TasksIter<Result<Indexed, Error>>,
+        Vec<Indexed>,
     ),
-    Filling(ProcessWrapper, TasksIter<(Mask, usize)>),
+    Filling(ProcessWrapper, TasksIter<Result<Vec<(Mask, usize)>, Error>>),
     Finished,
 }
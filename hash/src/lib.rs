@@ -0,0 +1,54 @@
+use image::{
+    imageops::{grayscale, resize, FilterType},
+    GenericImageView, Pixel,
+};
+
+/// The difference hash: whether each pixel of a 9x8 grayscale thumbnail is brighter than its
+/// right neighbor. Resized or slightly edited copies differ in a few bits only
+pub fn dhash<I>(img: &I) -> u64
+where
+    I: GenericImageView,
+    I::Pixel: Pixel<Subpixel = u8> + 'static,
+{
+    let thumb = resize(&grayscale(img), 9, 8, FilterType::Triangle);
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = thumb.get_pixel(x, y).0[0] > thumb.get_pixel(x + 1, y).0[0];
+            hash = hash << 1 | bit as u64;
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::dhash,
+        image::{
+            imageops::{resize, FilterType},
+            DynamicImage, RgbImage,
+        },
+    };
+
+    #[test]
+    fn near_duplicates() {
+        let img = RgbImage::from_fn(64, 48, |x, y| [(x * 4) as u8, (y * 5) as u8, 128].into());
+        let mut marked = img.clone();
+        // a small watermark
+        for x in 56..64 {
+            marked.put_pixel(x, 47, [255; 3].into());
+        }
+        let small = resize(&img, 32, 24, FilterType::Triangle);
+        let other = RgbImage::from_fn(64, 48, |x, y| {
+            [(255 - x * 4) as u8, (y * 5) as u8, 0].into()
+        });
+
+        let hash = dhash(&img);
+        assert!((hash ^ dhash(&marked)).count_ones() <= 4);
+        assert!((hash ^ dhash(&small)).count_ones() <= 4);
+        assert!((hash ^ dhash(&other)).count_ones() > 16);
+        // the same for the decoded images
+        assert_eq!(dhash(&DynamicImage::ImageRgb8(img)), hash);
+    }
+}